@@ -0,0 +1,94 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// 广播组：写入组的输入会发送到组内所有会话
+#[derive(Debug, Clone, Serialize)]
+pub struct BroadcastGroup {
+    pub name: String,
+    pub session_ids: Vec<u64>,
+}
+
+/// 广播到单个会话的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct BroadcastWrite {
+    pub session_id: u64,
+    /// 写入失败的原因，成功时为空
+    pub error: Option<String>,
+}
+
+/// 广播组成员关系，一个会话可以同时加入多个组
+#[derive(Default)]
+pub struct BroadcastGroups {
+    groups: BTreeMap<String, BTreeSet<u64>>,
+}
+
+impl BroadcastGroups {
+    /// 加入组，组不存在时创建；返回会话是否是新加入的
+    pub fn join(&mut self, group: &str, session_id: u64) -> bool {
+        self.groups
+            .entry(group.to_string())
+            .or_default()
+            .insert(session_id)
+    }
+
+    /// 离开组，组内没有会话时删除该组；返回会话原本是否在组内
+    pub fn leave(&mut self, group: &str, session_id: u64) -> bool {
+        let Some(members) = self.groups.get_mut(group) else {
+            return false;
+        };
+        let removed = members.remove(&session_id);
+        if members.is_empty() {
+            self.groups.remove(group);
+        }
+        removed
+    }
+
+    /// 会话关闭后从所有组中移除
+    pub fn remove_session(&mut self, session_id: u64) {
+        self.groups.retain(|_, members| {
+            members.remove(&session_id);
+            !members.is_empty()
+        });
+    }
+
+    /// 组内的会话，按编号排序
+    pub fn members(&self, group: &str) -> Option<Vec<u64>> {
+        self.groups
+            .get(group)
+            .map(|members| members.iter().copied().collect())
+    }
+
+    pub fn list(&self) -> Vec<BroadcastGroup> {
+        self.groups
+            .iter()
+            .map(|(name, members)| BroadcastGroup {
+                name: name.clone(),
+                session_ids: members.iter().copied().collect(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_and_leave() {
+        let mut groups = BroadcastGroups::default();
+        assert!(groups.join("web", 2));
+        assert!(groups.join("web", 1));
+        assert!(!groups.join("web", 1));
+        assert!(groups.join("db", 1));
+        assert_eq!(groups.members("web"), Some(vec![1, 2]));
+
+        assert!(groups.leave("web", 2));
+        assert!(!groups.leave("web", 2));
+        assert!(!groups.leave("missing", 1));
+
+        // 会话关闭后从所有组移除，空组随之删除
+        groups.remove_session(1);
+        assert!(groups.members("web").is_none());
+        assert!(groups.list().is_empty());
+    }
+}
@@ -1,5 +1,7 @@
+use crate::secrets::{parse_secret_ref, SecretStore};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::path::PathBuf;
@@ -8,6 +10,7 @@ use std::path::PathBuf;
 pub struct ClaudeProvider {
     pub name: String,
     pub base_url: String,
+    /// providers 列表中保存为密钥引用（`secret:claude/<name>`），返回给前端时解析为明文
     pub api_key: String,
     pub model: String,
     pub created_at: String,
@@ -42,7 +45,7 @@ impl ClaudeConfigManager {
         Ok(Self::get_config_dir()?.join("settings.json"))
     }
 
-    pub fn load_providers() -> Result<Vec<ClaudeProvider>, String> {
+    pub fn load_providers(secrets: &SecretStore) -> Result<Vec<ClaudeProvider>, String> {
         let config_file = Self::get_config_file()?;
 
         if !config_file.exists() {
@@ -57,11 +60,14 @@ impl ClaudeConfigManager {
 
         // 如果有 providers 字段，返回 providers
         if let Some(providers) = settings.get("providers").and_then(|p| p.as_array()) {
-            let result: Result<Vec<ClaudeProvider>, _> = providers
+            return providers
                 .iter()
-                .map(|p| serde_json::from_value(p.clone()))
+                .map(|p| {
+                    let provider = serde_json::from_value(p.clone())
+                        .map_err(|e| format!("无法解析提供商信息: {}", e))?;
+                    Self::resolve_api_key(provider, secrets)
+                })
                 .collect();
-            return result.map_err(|e| format!("无法解析提供商信息: {}", e));
         }
 
         // 否则，从现有配置生成一个提供商
@@ -81,13 +87,13 @@ impl ClaudeConfigManager {
                 model: model.to_string(),
                 created_at: chrono::Local::now().to_rfc3339(),
             };
-            return Ok(vec![provider]);
+            return Ok(vec![Self::resolve_api_key(provider, secrets)?]);
         }
 
         Ok(Vec::new())
     }
 
-    pub fn get_current_provider() -> Result<Option<ClaudeProvider>, String> {
+    pub fn get_current_provider(secrets: &SecretStore) -> Result<Option<ClaudeProvider>, String> {
         let config_file = Self::get_config_file()?;
 
         if !config_file.exists() {
@@ -106,9 +112,9 @@ impl ClaudeConfigManager {
                 for p in providers {
                     if let Some(name) = p.get("name").and_then(|n| n.as_str()) {
                         if name == current_name {
-                            return serde_json::from_value(p.clone())
-                                .map(Some)
-                                .map_err(|e| format!("无法解析提供商信息: {}", e));
+                            let provider = serde_json::from_value(p.clone())
+                                .map_err(|e| format!("无法解析提供商信息: {}", e))?;
+                            return Self::resolve_api_key(provider, secrets).map(Some);
                         }
                     }
                 }
@@ -132,13 +138,13 @@ impl ClaudeConfigManager {
                 model: model.to_string(),
                 created_at: chrono::Local::now().to_rfc3339(),
             };
-            return Ok(Some(provider));
+            return Self::resolve_api_key(provider, secrets).map(Some);
         }
 
         Ok(None)
     }
 
-    pub fn add_provider(provider: ClaudeProvider) -> Result<(), String> {
+    pub fn add_provider(mut provider: ClaudeProvider, secrets: &SecretStore) -> Result<(), String> {
         let config_file = Self::get_config_file()?;
 
         let mut settings: Value = if config_file.exists() {
@@ -168,6 +174,9 @@ impl ClaudeConfigManager {
             return Err(format!("提供商 '{}' 已存在", provider.name));
         }
 
+        // API Key 存入密钥存储，配置文件中只保留引用
+        provider.api_key = Self::store_api_key(&provider.name, &provider.api_key, secrets)?;
+
         providers
             .push(serde_json::to_value(&provider).map_err(|e| format!("无法序列化提供商: {}", e))?);
 
@@ -179,7 +188,7 @@ impl ClaudeConfigManager {
         Ok(())
     }
 
-    pub fn switch_provider(provider_name: String) -> Result<(), String> {
+    pub fn switch_provider(provider_name: String, secrets: &SecretStore) -> Result<(), String> {
         let config_file = Self::get_config_file()?;
 
         let mut settings: Value = if config_file.exists() {
@@ -228,6 +237,13 @@ impl ClaudeConfigManager {
 
         let provider = provider.ok_or(format!("提供商 '{}' 不存在", provider_name))?;
 
+        // 顶层 ANTHROPIC_API_KEY 同样只保存引用，明文在启动终端时注入环境变量
+        let api_key = provider
+            .get("api_key")
+            .and_then(|v| v.as_str())
+            .map(|api_key| Self::store_api_key(&provider_name, api_key, secrets))
+            .transpose()?;
+
         // 更新 current_provider
         settings["current_provider"] = json!(provider_name.clone());
 
@@ -238,7 +254,7 @@ impl ClaudeConfigManager {
         if let Some(base_url) = provider.get("base_url").and_then(|v| v.as_str()) {
             settings["ANTHROPIC_BASE_URL"] = json!(base_url);
         }
-        if let Some(api_key) = provider.get("api_key").and_then(|v| v.as_str()) {
+        if let Some(api_key) = &api_key {
             settings["ANTHROPIC_API_KEY"] = json!(api_key);
         }
         if let Some(model) = provider.get("model").and_then(|v| v.as_str()) {
@@ -246,10 +262,8 @@ impl ClaudeConfigManager {
         }
 
         // 生成环境变量文件
-        if let Some(api_key) = provider.get("api_key").and_then(|v| v.as_str()) {
-            if let Some(base_url) = provider.get("base_url").and_then(|v| v.as_str()) {
-                Self::set_env_vars_with_values(api_key, base_url)?;
-            }
+        if let Some(base_url) = provider.get("base_url").and_then(|v| v.as_str()) {
+            Self::set_env_vars_with_values(base_url)?;
         }
 
         let json = serde_json::to_string_pretty(&settings)
@@ -260,7 +274,7 @@ impl ClaudeConfigManager {
         Ok(())
     }
 
-    pub fn remove_provider(provider_name: String) -> Result<(), String> {
+    pub fn remove_provider(provider_name: String, secrets: &SecretStore) -> Result<(), String> {
         let config_file = Self::get_config_file()?;
 
         let mut settings: Value = if config_file.exists() {
@@ -288,6 +302,9 @@ impl ClaudeConfigManager {
 
         fs::write(&config_file, json).map_err(|e| format!("无法保存配置文件: {}", e))?;
 
+        // 配置保存成功后再删除密钥存储中的 API Key
+        secrets.delete(&Self::api_key_secret_id(&provider_name))?;
+
         Ok(())
     }
 
@@ -304,18 +321,191 @@ impl ClaudeConfigManager {
         Ok(valid)
     }
 
-    fn set_env_vars_with_values(api_key: &str, base_url: &str) -> Result<(), String> {
-        // 创建 .env 文件
+    fn set_env_vars_with_values(base_url: &str) -> Result<(), String> {
+        // 创建 .env 文件；API Key 不写入磁盘，由 launch_env 注入终端环境
         let config_dir = Self::get_config_dir()?;
         let env_file = config_dir.join(".env.claude");
 
-        let env_content = format!(
-            "ANTHROPIC_API_KEY={}\nANTHROPIC_BASE_URL={}\n",
-            api_key, base_url
-        );
+        let env_content = format!("ANTHROPIC_BASE_URL={}\n", base_url);
 
         fs::write(&env_file, env_content).map_err(|e| format!("无法写入 .env 文件: {}", e))?;
 
         Ok(())
     }
+
+    /// 当前提供商的环境变量（API Key 从密钥存储解析为明文），注入本地终端供 Claude 工具读取
+    pub fn launch_env(secrets: &SecretStore) -> Result<HashMap<String, String>, String> {
+        let Some(provider) = Self::get_current_provider(secrets)? else {
+            return Ok(HashMap::new());
+        };
+        Ok(HashMap::from([
+            ("ANTHROPIC_API_KEY".to_string(), provider.api_key),
+            ("ANTHROPIC_BASE_URL".to_string(), provider.base_url),
+            ("ANTHROPIC_MODEL".to_string(), provider.model),
+        ]))
+    }
+
+    // 把提供商的 API Key 引用解析为明文
+    fn resolve_api_key(
+        mut provider: ClaudeProvider,
+        secrets: &SecretStore,
+    ) -> Result<ClaudeProvider, String> {
+        provider.api_key = secrets.resolve(&provider.api_key)?;
+        Ok(provider)
+    }
+
+    fn api_key_secret_id(provider_name: &str) -> String {
+        format!("claude/{}", provider_name)
+    }
+
+    // 将 API Key 存入密钥存储并返回引用；已经是引用的值原样返回
+    fn store_api_key(
+        provider_name: &str,
+        api_key: &str,
+        secrets: &SecretStore,
+    ) -> Result<String, String> {
+        if parse_secret_ref(api_key).is_some() {
+            return Ok(api_key.to_string());
+        }
+        secrets.put(&Self::api_key_secret_id(provider_name), api_key)
+    }
+
+    /// 将 settings.json 和 .env.claude 中的明文 API Key 迁移到密钥存储
+    ///
+    /// 只在用户主动调用时执行，返回迁移的密钥数量
+    pub fn migrate_plaintext_secrets(secrets: &SecretStore) -> Result<usize, String> {
+        let config_file = Self::get_config_file()?;
+        if !config_file.exists() {
+            return Ok(0);
+        }
+
+        let content =
+            fs::read_to_string(&config_file).map_err(|e| format!("无法读取配置文件: {}", e))?;
+        let mut settings: Value =
+            serde_json::from_str(&content).map_err(|e| format!("无法解析配置文件: {}", e))?;
+
+        let original = settings.clone();
+        let migrated = Self::migrate_settings(&mut settings, secrets)?;
+        if settings != original {
+            let json = serde_json::to_string_pretty(&settings)
+                .map_err(|e| format!("无法序列化配置: {}", e))?;
+            fs::write(&config_file, json).map_err(|e| format!("无法保存配置文件: {}", e))?;
+        }
+
+        // .env.claude 中的明文 API Key 已在 settings.json 中有对应引用，直接删除该行
+        let env_file = Self::get_config_dir()?.join(".env.claude");
+        if let Ok(content) = fs::read_to_string(&env_file) {
+            if let Some(stripped) = Self::strip_env_api_key(&content) {
+                fs::write(&env_file, stripped).map_err(|e| format!("无法写入 .env 文件: {}", e))?;
+            }
+        }
+
+        Ok(migrated)
+    }
+
+    fn migrate_settings(settings: &mut Value, secrets: &SecretStore) -> Result<usize, String> {
+        let mut migrated = 0;
+
+        if let Some(providers) = settings.get_mut("providers").and_then(|p| p.as_array_mut()) {
+            for provider in providers {
+                let (Some(name), Some(api_key)) = (
+                    provider.get("name").and_then(|v| v.as_str()),
+                    provider.get("api_key").and_then(|v| v.as_str()),
+                ) else {
+                    continue;
+                };
+                if api_key.is_empty() || parse_secret_ref(api_key).is_some() {
+                    continue;
+                }
+
+                provider["api_key"] = json!(Self::store_api_key(name, api_key, secrets)?);
+                migrated += 1;
+            }
+        }
+
+        // 顶层 ANTHROPIC_API_KEY 归属当前提供商
+        if let Some(api_key) = settings.get("ANTHROPIC_API_KEY").and_then(|v| v.as_str()) {
+            if !api_key.is_empty() && parse_secret_ref(api_key).is_none() {
+                let name = settings
+                    .get("current_provider")
+                    .or_else(|| settings.get("activeProvider"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("default");
+                let reference = Self::store_api_key(name, api_key, secrets)?;
+                settings["ANTHROPIC_API_KEY"] = json!(reference);
+                migrated += 1;
+            }
+        }
+
+        Ok(migrated)
+    }
+
+    // 删除 .env 内容中的 ANTHROPIC_API_KEY 行，没有该行时返回 None
+    fn strip_env_api_key(content: &str) -> Option<String> {
+        let mut found = false;
+        let stripped: String = content
+            .lines()
+            .filter(|line| {
+                let is_key = line.starts_with("ANTHROPIC_API_KEY=");
+                found |= is_key;
+                !is_key
+            })
+            .map(|line| format!("{}\n", line))
+            .collect();
+        found.then_some(stripped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_settings() {
+        let dir = std::env::temp_dir().join(format!("huaan_claude_migrate_{}", std::process::id()));
+        let secrets = SecretStore::open_file(&dir).unwrap();
+
+        secrets.put("claude/done", "sk-done").unwrap();
+
+        let mut settings = json!({
+            "providers": [
+                { "name": "work", "base_url": "https://api.example.com", "api_key": "sk-work", "model": "m", "created_at": "" },
+                { "name": "done", "base_url": "https://api.example.com", "api_key": "secret:claude/done", "model": "m", "created_at": "" }
+            ],
+            "activeProvider": "done",
+            "ANTHROPIC_API_KEY": "sk-done"
+        });
+
+        assert_eq!(
+            ClaudeConfigManager::migrate_settings(&mut settings, &secrets).unwrap(),
+            2
+        );
+        assert_eq!(settings["providers"][0]["api_key"], "secret:claude/work");
+        assert_eq!(settings["providers"][1]["api_key"], "secret:claude/done");
+        assert_eq!(settings["ANTHROPIC_API_KEY"], "secret:claude/done");
+        assert_eq!(secrets.get("claude/work").unwrap(), "sk-work");
+        assert_eq!(secrets.get("claude/done").unwrap(), "sk-done");
+
+        // 再次迁移不应有变化
+        assert_eq!(
+            ClaudeConfigManager::migrate_settings(&mut settings, &secrets).unwrap(),
+            0
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_strip_env_api_key() {
+        assert_eq!(
+            ClaudeConfigManager::strip_env_api_key(
+                "ANTHROPIC_API_KEY=sk-work\nANTHROPIC_BASE_URL=https://api.example.com\n"
+            )
+            .as_deref(),
+            Some("ANTHROPIC_BASE_URL=https://api.example.com\n")
+        );
+        assert_eq!(
+            ClaudeConfigManager::strip_env_api_key("ANTHROPIC_BASE_URL=https://api.example.com\n"),
+            None
+        );
+    }
 }
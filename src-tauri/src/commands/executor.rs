@@ -88,7 +88,7 @@ fn check_privileged_command(cmd: &str) -> bool {
 }
 
 /// 展开路径中的 ~ 符号为用户主目录
-fn expand_tilde(path: &str) -> Result<String, String> {
+pub(crate) fn expand_tilde(path: &str) -> Result<String, String> {
     if path == "~" || path.starts_with("~/") {
         let home = std::env::var("HOME")
             .or_else(|_| std::env::var("USERPROFILE"))
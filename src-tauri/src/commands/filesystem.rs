@@ -17,7 +17,7 @@ pub struct FileInfo {
 }
 
 /// 检查路径是否为敏感系统路径
-fn is_sensitive_path(path: &str) -> bool {
+pub(crate) fn is_sensitive_path(path: &str) -> bool {
     let sensitive_patterns = vec![
         "/etc/passwd",
         "/etc/shadow",
@@ -89,7 +89,7 @@ fn normalize_path(path: &str) -> Result<PathBuf, String> {
 }
 
 /// 格式化系统时间为字符串
-fn format_system_time(time: SystemTime) -> String {
+pub(crate) fn format_system_time(time: SystemTime) -> String {
     let datetime: DateTime<Local> = time.into();
     datetime.format("%Y-%m-%d %H:%M:%S").to_string()
 }
@@ -266,14 +266,17 @@ pub async fn list_files(dir: String, show_hidden: Option<bool>) -> Result<Vec<Fi
         }
     }
 
-    // 按名称排序（目录在前）
+    sort_files(&mut files);
+    Ok(files)
+}
+
+/// 按名称排序（目录在前）
+pub(crate) fn sort_files(files: &mut [FileInfo]) {
     files.sort_by(|a, b| match (a.is_dir, b.is_dir) {
         (true, false) => std::cmp::Ordering::Less,
         (false, true) => std::cmp::Ordering::Greater,
         _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
     });
-
-    Ok(files)
 }
 
 /// 创建目录
@@ -1,23 +1,48 @@
+mod broadcast;
 mod claude_config;
 mod commands;
+mod process_info;
+mod recording;
+mod scrollback;
+mod search;
+mod secrets;
+mod sftp;
+mod shell_integration;
+mod ssh;
+mod ssh_forward;
+mod ssh_profiles;
 mod task;
 mod terminal;
 
+use broadcast::{BroadcastGroup, BroadcastWrite};
 use claude_config::{ClaudeConfigManager, ClaudeProvider};
+use process_info::TerminalProcessInfo;
+use scrollback::ScrollbackChunk;
+use search::SearchMatch;
+use secrets::{SecretStore, SecretStoreStatus};
 use serde::{Deserialize, Serialize};
+use sftp::{SftpManager, SftpProgress};
+use shell_integration::CommandRecord;
+use ssh::{HostKeyPromptKind, SshConnectOptions, SshHop, SshKeepalive, SshReconnect};
+use ssh_forward::{ForwardSpec, ForwardStatus};
+use ssh_profiles::{SshProfile, SshProfileStore};
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::Arc;
-use task::{Task, TaskManager};
-use tauri::{AppHandle, State};
-use terminal::TerminalManager;
+use task::{Task, TaskManager, TaskShell};
+use tauri::{AppHandle, Manager, State};
+use terminal::{
+    CommandWithOutput, TerminalBytes, TerminalInfo, TerminalManager, TerminalOptions,
+    TerminalScreen,
+};
 use tokio::sync::Mutex;
-use tracing::info;
+use tracing::{info, warn};
 
 struct AppState {
     terminal_manager: Arc<TerminalManager>,
     task_manager: Arc<Mutex<TaskManager>>,
+    sftp_manager: Arc<SftpManager>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -34,16 +59,43 @@ struct ProjectStructure {
     files: Vec<FileInfo>,
 }
 
+// 跳板机参数，字段与 start_ssh_terminal 的连接参数相同
+#[derive(Deserialize)]
+struct JumpHost {
+    host: Option<String>,
+    port: Option<u16>,
+    username: Option<String>,
+    password: Option<String>,
+    password_ref: Option<String>,
+    identity_file: Option<String>,
+    passphrase: Option<String>,
+    profile: Option<String>,
+}
+
 #[tauri::command]
 fn start_terminal(
     session_id: u64,
     shell_type: Option<String>,
+    options: Option<TerminalOptions>,
     app_handle: AppHandle,
     state: State<AppState>,
+    secrets: State<SecretStore>,
 ) -> Result<(), String> {
+    let mut options = options.unwrap_or_default();
+    // 注入当前 Claude 提供商的 API Key，磁盘上只保存引用；显式传入或移除的变量优先
+    match ClaudeConfigManager::launch_env(&secrets) {
+        Ok(env) => {
+            for (key, value) in env {
+                if !options.env_remove.contains(&key) {
+                    options.env.entry(key).or_insert(value);
+                }
+            }
+        }
+        Err(e) => warn!("无法读取 Claude 提供商的环境变量: {}", e),
+    }
     state
         .terminal_manager
-        .start_terminal(session_id, shell_type, app_handle)
+        .start_terminal(session_id, shell_type, options, app_handle)
         .map_err(|e| e.to_string())
 }
 
@@ -55,6 +107,31 @@ fn write_terminal(session_id: u64, data: String, state: State<AppState>) -> Resu
         .map_err(|e| e.to_string())
 }
 
+// 写入原始字节，data 可以是 base64 字符串或字节数组
+#[tauri::command]
+fn write_terminal_bytes(
+    session_id: u64,
+    data: TerminalBytes,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let bytes = data
+        .into_bytes()
+        .map_err(|e| format!("无效的终端数据: {}", e))?;
+    state
+        .terminal_manager
+        .write_bytes(session_id, &bytes)
+        .map_err(|e| e.to_string())
+}
+
+// 粘贴文本，程序开启 bracketed paste 模式时自动添加粘贴标记
+#[tauri::command]
+fn paste_terminal(session_id: u64, text: String, state: State<AppState>) -> Result<(), String> {
+    state
+        .terminal_manager
+        .paste_terminal(session_id, &text)
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 fn resize_terminal(
     session_id: u64,
@@ -69,13 +146,30 @@ fn resize_terminal(
 }
 
 #[tauri::command]
-fn close_terminal(session_id: u64, state: State<AppState>) -> Result<(), String> {
+fn close_terminal(
+    session_id: u64,
+    force: Option<bool>,
+    state: State<AppState>,
+) -> Result<(), String> {
     state
         .terminal_manager
-        .close_terminal(session_id)
+        .close_terminal(session_id, force.unwrap_or(false))
         .map_err(|e| e.to_string())
 }
 
+// 会话中正在运行的进程：前台进程组、命令行、CPU 和内存占用（读取 /proc，仅支持 Linux）
+#[tauri::command]
+async fn get_terminal_process_info(
+    session_id: u64,
+    state: State<'_, AppState>,
+) -> Result<TerminalProcessInfo, String> {
+    let shell_pid = state
+        .terminal_manager
+        .shell_pid(session_id)
+        .map_err(|e| e.to_string())?;
+    process_info::inspect(session_id, shell_pid).await
+}
+
 #[tauri::command]
 fn get_current_dir(session_id: u64, state: State<AppState>) -> Result<String, String> {
     state
@@ -84,22 +178,681 @@ fn get_current_dir(session_id: u64, state: State<AppState>) -> Result<String, St
         .map_err(|e| e.to_string())
 }
 
+// 获取 since_offset 之后的历史输出，用于前端重新加载或多窗口附加
+#[tauri::command]
+fn get_terminal_scrollback(
+    session_id: u64,
+    since_offset: Option<u64>,
+    state: State<AppState>,
+) -> Result<ScrollbackChunk, String> {
+    state
+        .terminal_manager
+        .get_scrollback(session_id, since_offset.unwrap_or(0))
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
-fn start_ssh_terminal(
+fn list_terminals(state: State<AppState>) -> Vec<TerminalInfo> {
+    state.terminal_manager.list_terminals()
+}
+
+// 重新附加到已有会话，返回 since_offset 之后的历史输出
+#[tauri::command]
+fn attach_terminal(
     session_id: u64,
-    host: String,
-    port: u16,
-    username: String,
+    since_offset: Option<u64>,
+    state: State<AppState>,
+) -> Result<ScrollbackChunk, String> {
+    state
+        .terminal_manager
+        .attach_terminal(session_id, since_offset.unwrap_or(0))
+        .map_err(|e| e.to_string())
+}
+
+// 断开前端但保留会话，shell 在后台继续运行
+#[tauri::command]
+fn detach_terminal(session_id: u64, state: State<AppState>) -> Result<(), String> {
+    state
+        .terminal_manager
+        .detach_terminal(session_id)
+        .map_err(|e| e.to_string())
+}
+
+// 前端确认已写入终端的输出偏移量，用于流量控制
+#[tauri::command]
+fn ack_terminal_output(session_id: u64, offset: u64, state: State<AppState>) -> Result<(), String> {
+    state
+        .terminal_manager
+        .ack_output(session_id, offset)
+        .map_err(|e| e.to_string())
+}
+
+// 会话中通过 OSC 133 记录的命令
+#[tauri::command]
+fn list_terminal_commands(
+    session_id: u64,
+    state: State<AppState>,
+) -> Result<Vec<CommandRecord>, String> {
+    state
+        .terminal_manager
+        .list_commands(session_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_terminal_command_output(
+    session_id: u64,
+    command_id: u64,
+    state: State<AppState>,
+) -> Result<String, String> {
+    state
+        .terminal_manager
+        .get_command_output(session_id, command_id)
+        .map_err(|e| e.to_string())
+}
+
+// 最近一条失败的命令及其输出，供 AI 分析
+#[tauri::command]
+fn get_last_failed_command(
+    session_id: u64,
+    state: State<AppState>,
+) -> Result<Option<CommandWithOutput>, String> {
+    state
+        .terminal_manager
+        .last_failed_command(session_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn set_terminal_scrollback_size(bytes: usize, state: State<AppState>) {
+    state.terminal_manager.set_scrollback_size(bytes);
+}
+
+// 会话当前屏幕的文本、光标位置和是否处于备用屏幕，可用于获取 vim、htop 等全屏程序的画面
+#[tauri::command]
+fn get_terminal_screen(session_id: u64, state: State<AppState>) -> Result<TerminalScreen, String> {
+    state
+        .terminal_manager
+        .get_screen(session_id)
+        .map_err(|e| e.to_string())
+}
+
+// 加入广播组，写入该组的输入会发送到组内所有会话
+#[tauri::command]
+fn join_broadcast_group(
+    group: String,
+    session_id: u64,
+    state: State<AppState>,
+) -> Result<(), String> {
+    state
+        .terminal_manager
+        .join_broadcast_group(&group, session_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn leave_broadcast_group(group: String, session_id: u64, state: State<AppState>) {
+    state
+        .terminal_manager
+        .leave_broadcast_group(&group, session_id);
+}
+
+#[tauri::command]
+fn list_broadcast_groups(state: State<AppState>) -> Vec<BroadcastGroup> {
+    state.terminal_manager.list_broadcast_groups()
+}
+
+// 向广播组内的所有会话写入相同的输入，返回每个会话的写入结果
+#[tauri::command]
+fn broadcast_terminal(
+    group: String,
+    data: String,
+    state: State<AppState>,
+) -> Result<Vec<BroadcastWrite>, String> {
+    state
+        .terminal_manager
+        .broadcast(&group, &data)
+        .map_err(|e| e.to_string())
+}
+
+// 搜索会话输出（已去掉转义序列），不指定 session_id 时搜索所有打开的会话
+#[tauri::command]
+fn search_terminal(
+    session_id: Option<u64>,
+    pattern: String,
+    regex: Option<bool>,
+    case_sensitive: Option<bool>,
+    state: State<AppState>,
+) -> Result<Vec<SearchMatch>, String> {
+    state
+        .terminal_manager
+        .search(
+            session_id,
+            &pattern,
+            regex.unwrap_or(false),
+            case_sensitive.unwrap_or(false),
+        )
+        .map_err(|e| e.to_string())
+}
+
+// 开始录制终端会话（asciicast v2），未指定路径时保存到应用数据目录的 recordings 下
+// 指定的文件已存在时，需要传入 overwrite 才会覆盖
+#[tauri::command]
+fn start_recording(
+    session_id: u64,
+    path: Option<String>,
+    record_input: Option<bool>,
+    overwrite: Option<bool>,
+    state: State<AppState>,
+    app_handle: AppHandle,
+) -> Result<String, String> {
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => app_handle
+            .path()
+            .app_data_dir()
+            .map_err(|e| e.to_string())?
+            .join("recordings")
+            .join(format!(
+                "session-{}-{}.cast",
+                session_id,
+                chrono::Local::now().format("%Y%m%d-%H%M%S")
+            )),
+    };
+    state
+        .terminal_manager
+        .start_recording(
+            session_id,
+            path,
+            record_input.unwrap_or(false),
+            overwrite.unwrap_or(false),
+        )
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn stop_recording(session_id: u64, state: State<AppState>) -> Result<String, String> {
+    state
+        .terminal_manager
+        .stop_recording(session_id)
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|e| e.to_string())
+}
+
+// 通过 terminal-output-{session_id} 事件回放录制文件
+#[tauri::command]
+async fn replay_recording(
+    session_id: u64,
+    path: String,
+    speed: Option<f64>,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    state
+        .terminal_manager
+        .replay_recording(
+            session_id,
+            PathBuf::from(path),
+            speed.unwrap_or(1.0),
+            app_handle,
+        )
+        .map_err(|e| e.to_string())
+}
+
+// 传入 profile 时使用已保存的 SSH 配置，否则使用 host/port/username
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn start_ssh_terminal(
+    session_id: u64,
+    host: Option<String>,
+    port: Option<u16>,
+    username: Option<String>,
     password: Option<String>,
+    password_ref: Option<String>,
+    identity_file: Option<String>,
+    passphrase: Option<String>,
+    profile: Option<String>,
+    forwards: Option<Vec<ForwardSpec>>,
+    jump_hosts: Option<Vec<JumpHost>>,
+    keepalive: Option<SshKeepalive>,
+    reconnect: Option<SshReconnect>,
     app_handle: AppHandle,
+    state: State<'_, AppState>,
+    secrets: State<'_, SecretStore>,
+) -> Result<(), String> {
+    let mut options = ssh_connect_options(
+        host,
+        port,
+        username,
+        password,
+        password_ref,
+        identity_file,
+        passphrase,
+        profile,
+        &app_handle,
+        &secrets,
+    )?;
+    // 传入的跳板机替换配置中的跳板机，传入空列表表示直连
+    if let Some(jump_hosts) = jump_hosts {
+        options.jump_hosts =
+            resolve_jump_hosts(jump_hosts, &options.username, &app_handle, &secrets)?;
+    }
+    // 传入的保活和重连设置覆盖配置中的设置
+    if let Some(keepalive) = keepalive {
+        options.keepalive = keepalive;
+    }
+    if let Some(reconnect) = reconnect {
+        options.reconnect = reconnect;
+    }
+    // 传入的转发追加在配置中的转发之后
+    options.forwards.extend(forwards.unwrap_or_default());
+    state
+        .terminal_manager
+        .start_ssh_terminal(session_id, options, app_handle)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// SSH 终端和 SFTP 共用的连接参数解析
+#[allow(clippy::too_many_arguments)]
+fn ssh_connect_options(
+    host: Option<String>,
+    port: Option<u16>,
+    username: Option<String>,
+    password: Option<String>,
+    password_ref: Option<String>,
+    identity_file: Option<String>,
+    passphrase: Option<String>,
+    profile: Option<String>,
+    app_handle: &AppHandle,
+    secrets: &SecretStore,
+) -> Result<SshConnectOptions, String> {
+    // 密码可以直接传入，也可以是密钥存储中的引用
+    let resolve_password = |password_ref: Option<String>| -> Result<Option<String>, String> {
+        match (password, password_ref) {
+            (Some(password), _) => Ok(Some(password)),
+            (None, Some(password_ref)) => secrets.resolve(&password_ref).map(Some),
+            (None, None) => Ok(None),
+        }
+    };
+
+    if let Some(name) = profile {
+        let store = SshProfileStore::open(app_handle)?;
+        let profile = store.get(&name)?;
+        let password = resolve_password(password_ref.or_else(|| profile.password_ref.clone()))?;
+        // 跳板机引用的配置使用它们自己保存的密码
+        let jump_password = |jump: &SshProfile| {
+            jump.password_ref
+                .as_deref()
+                .map(|password_ref| secrets.resolve(password_ref))
+                .transpose()
+        };
+        SshConnectOptions::from_profile(
+            &profile,
+            password,
+            passphrase,
+            &store.list()?,
+            &jump_password,
+        )
+        .map_err(|e| e.to_string())
+    } else {
+        let host = host.ok_or("缺少 SSH 主机地址")?;
+        let username = username.ok_or("缺少 SSH 用户名")?;
+        let password = resolve_password(password_ref)?;
+        Ok(SshConnectOptions::new(
+            host,
+            port.unwrap_or(22),
+            username,
+            password,
+            identity_file,
+            passphrase,
+        ))
+    }
+}
+
+// 按顺序解析跳板机，未指定用户名时沿用目标主机的用户名；
+// 引用的配置本身带有跳板机时，这些跳板机排在它之前
+fn resolve_jump_hosts(
+    jump_hosts: Vec<JumpHost>,
+    default_user: &str,
+    app_handle: &AppHandle,
+    secrets: &SecretStore,
+) -> Result<Vec<SshHop>, String> {
+    let mut hops = Vec::new();
+    for (index, jump) in jump_hosts.into_iter().enumerate() {
+        let options = ssh_connect_options(
+            jump.host,
+            jump.port,
+            jump.username.or_else(|| Some(default_user.to_string())),
+            jump.password,
+            jump.password_ref,
+            jump.identity_file,
+            jump.passphrase,
+            jump.profile,
+            app_handle,
+            secrets,
+        )
+        .map_err(|e| format!("第 {} 个跳板机参数无效: {}", index + 1, e))?;
+        hops.extend(options.jump_hosts.iter().cloned());
+        hops.push(options.target());
+    }
+    Ok(hops)
+}
+
+// 应答 ssh-hostkey-prompt；kind 取自事件负载，省略时视为终端会话
+#[tauri::command]
+fn respond_ssh_hostkey(
+    session_id: u64,
+    accept: bool,
+    kind: Option<HostKeyPromptKind>,
     state: State<AppState>,
 ) -> Result<(), String> {
     state
         .terminal_manager
-        .start_ssh_terminal(session_id, host, port, username, password, app_handle)
+        .respond_host_key(kind.unwrap_or_default(), session_id, accept)
+        .map_err(|e| e.to_string())
+}
+
+// 列出 SSH 会话的端口转发（-L / -R / -D）
+#[tauri::command]
+fn list_ssh_forwards(
+    session_id: u64,
+    state: State<AppState>,
+) -> Result<Vec<ForwardStatus>, String> {
+    state
+        .terminal_manager
+        .list_forwards(session_id)
         .map_err(|e| e.to_string())
 }
 
+// 在已连接的 SSH 会话上新增端口转发，监听失败时返回 failed 状态并推送 ssh-forward-{id} 事件
+#[tauri::command]
+async fn add_ssh_forward(
+    session_id: u64,
+    forward: ForwardSpec,
+    state: State<'_, AppState>,
+) -> Result<ForwardStatus, String> {
+    state
+        .terminal_manager
+        .add_forward(session_id, forward)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn remove_ssh_forward(
+    session_id: u64,
+    forward_id: u64,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .terminal_manager
+        .remove_forward(session_id, forward_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// SSH 连接配置管理命令
+
+#[tauri::command]
+fn list_ssh_profiles(app_handle: AppHandle) -> Result<Vec<SshProfile>, String> {
+    SshProfileStore::open(&app_handle)?.list()
+}
+
+#[tauri::command]
+fn get_ssh_profile(name: String, app_handle: AppHandle) -> Result<SshProfile, String> {
+    SshProfileStore::open(&app_handle)?.get(&name)
+}
+
+#[tauri::command]
+fn save_ssh_profile(profile: SshProfile, app_handle: AppHandle) -> Result<(), String> {
+    SshProfileStore::open(&app_handle)?.save(profile)
+}
+
+#[tauri::command]
+fn rename_ssh_profile(
+    old_name: String,
+    new_name: String,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    SshProfileStore::open(&app_handle)?.rename(&old_name, &new_name)
+}
+
+#[tauri::command]
+fn delete_ssh_profile(name: String, app_handle: AppHandle) -> Result<(), String> {
+    SshProfileStore::open(&app_handle)?.remove(&name)
+}
+
+// 从 ~/.ssh/config（或指定文件）导入 Host 块
+#[tauri::command]
+fn import_ssh_config(
+    path: Option<String>,
+    app_handle: AppHandle,
+) -> Result<Vec<SshProfile>, String> {
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => ssh_profiles::default_ssh_config_path()?,
+    };
+    SshProfileStore::open(&app_handle)?.import_ssh_config(&path)
+}
+
+// 建立 SFTP 连接，参数与 start_ssh_terminal 相同；返回远程主目录
+//
+// 主机密钥提示事件负载中的 kind 为 "sftp"，用于和同编号的终端会话区分；
+// 应答时需要把 kind 原样传给 respond_ssh_hostkey
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn sftp_connect(
+    connection_id: u64,
+    host: Option<String>,
+    port: Option<u16>,
+    username: Option<String>,
+    password: Option<String>,
+    password_ref: Option<String>,
+    identity_file: Option<String>,
+    passphrase: Option<String>,
+    profile: Option<String>,
+    jump_hosts: Option<Vec<JumpHost>>,
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+    secrets: State<'_, SecretStore>,
+) -> Result<String, String> {
+    let mut options = ssh_connect_options(
+        host,
+        port,
+        username,
+        password,
+        password_ref,
+        identity_file,
+        passphrase,
+        profile,
+        &app_handle,
+        &secrets,
+    )?;
+    if let Some(jump_hosts) = jump_hosts {
+        options.jump_hosts =
+            resolve_jump_hosts(jump_hosts, &options.username, &app_handle, &secrets)?;
+    }
+    state
+        .sftp_manager
+        .connect(connection_id, options, app_handle)
+        .await
+}
+
+#[tauri::command]
+async fn sftp_disconnect(connection_id: u64, state: State<'_, AppState>) -> Result<(), String> {
+    state.sftp_manager.disconnect(connection_id).await
+}
+
+// 以下远程文件命令与 commands::filesystem 中的本地命令参数一致
+#[tauri::command]
+async fn sftp_read_file(
+    connection_id: u64,
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    state
+        .sftp_manager
+        .get(connection_id)?
+        .read_file(&path)
+        .await
+}
+
+#[tauri::command]
+async fn sftp_write_file(
+    connection_id: u64,
+    path: String,
+    content: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .sftp_manager
+        .get(connection_id)?
+        .write_file(&path, &content)
+        .await
+}
+
+#[tauri::command]
+async fn sftp_list_files(
+    connection_id: u64,
+    dir: String,
+    show_hidden: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<Vec<commands::filesystem::FileInfo>, String> {
+    state
+        .sftp_manager
+        .get(connection_id)?
+        .list_files(&dir, show_hidden.unwrap_or(false))
+        .await
+}
+
+#[tauri::command]
+async fn sftp_create_directory(
+    connection_id: u64,
+    path: String,
+    recursive: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .sftp_manager
+        .get(connection_id)?
+        .create_directory(&path, recursive.unwrap_or(false))
+        .await
+}
+
+#[tauri::command]
+async fn sftp_delete_file(
+    connection_id: u64,
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .sftp_manager
+        .get(connection_id)?
+        .delete_file(&path)
+        .await
+}
+
+#[tauri::command]
+async fn sftp_delete_directory(
+    connection_id: u64,
+    path: String,
+    recursive: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .sftp_manager
+        .get(connection_id)?
+        .delete_directory(&path, recursive.unwrap_or(false))
+        .await
+}
+
+#[tauri::command]
+async fn sftp_copy_file(
+    connection_id: u64,
+    source: String,
+    destination: String,
+    overwrite: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .sftp_manager
+        .get(connection_id)?
+        .copy_file(&source, &destination, overwrite.unwrap_or(false))
+        .await
+}
+
+#[tauri::command]
+async fn sftp_move_file(
+    connection_id: u64,
+    source: String,
+    destination: String,
+    overwrite: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .sftp_manager
+        .get(connection_id)?
+        .move_file(&source, &destination, overwrite.unwrap_or(false))
+        .await
+}
+
+#[tauri::command]
+async fn sftp_path_exists(
+    connection_id: u64,
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    state
+        .sftp_manager
+        .get(connection_id)?
+        .path_exists(&path)
+        .await
+}
+
+#[tauri::command]
+async fn sftp_get_file_metadata(
+    connection_id: u64,
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<commands::filesystem::FileInfo, String> {
+    state
+        .sftp_manager
+        .get(connection_id)?
+        .get_file_metadata(&path)
+        .await
+}
+
+// 上传下载在完成后返回，期间通过 sftp-progress-{connection_id} 事件推送进度
+#[tauri::command]
+async fn sftp_upload(
+    connection_id: u64,
+    local_path: String,
+    remote_path: String,
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<SftpProgress, String> {
+    state
+        .sftp_manager
+        .upload(connection_id, &local_path, &remote_path, app_handle)
+        .await
+}
+
+#[tauri::command]
+async fn sftp_download(
+    connection_id: u64,
+    remote_path: String,
+    local_path: String,
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<SftpProgress, String> {
+    state
+        .sftp_manager
+        .download(connection_id, &remote_path, &local_path, app_handle)
+        .await
+}
+
 // 执行命令并返回结果（用于 AI 分析）
 #[tauri::command]
 async fn execute_command(
@@ -218,18 +971,32 @@ fn get_home_directory() -> Result<String, String> {
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn create_task(
     id: String,
     name: String,
     command: String,
     env_vars: Option<HashMap<String, String>>,
+    depends_on: Option<Vec<String>>,
+    working_dir: Option<String>,
+    shell: Option<TaskShell>,
+    args: Option<Vec<String>>,
     state: State<'_, AppState>,
 ) -> Result<Task, String> {
     state
         .task_manager
         .lock()
         .await
-        .create_task(id, name, command, env_vars)
+        .create_task(
+            id,
+            name,
+            command,
+            env_vars,
+            depends_on,
+            working_dir,
+            shell,
+            args,
+        )
         .await
         .map_err(|e| e.to_string())
 }
@@ -304,13 +1071,15 @@ fn get_home_dir() -> Result<String, String> {
 // Claude 配置管理命令
 
 #[tauri::command]
-fn load_claude_providers() -> Result<Vec<ClaudeProvider>, String> {
-    ClaudeConfigManager::load_providers()
+fn load_claude_providers(secrets: State<SecretStore>) -> Result<Vec<ClaudeProvider>, String> {
+    ClaudeConfigManager::load_providers(&secrets)
 }
 
 #[tauri::command]
-fn get_current_claude_provider() -> Result<Option<ClaudeProvider>, String> {
-    ClaudeConfigManager::get_current_provider()
+fn get_current_claude_provider(
+    secrets: State<SecretStore>,
+) -> Result<Option<ClaudeProvider>, String> {
+    ClaudeConfigManager::get_current_provider(&secrets)
 }
 
 #[tauri::command]
@@ -319,6 +1088,7 @@ fn add_claude_provider(
     base_url: String,
     api_key: String,
     model: String,
+    secrets: State<SecretStore>,
 ) -> Result<(), String> {
     let provider = ClaudeProvider {
         name,
@@ -327,17 +1097,61 @@ fn add_claude_provider(
         model,
         created_at: chrono::Local::now().to_rfc3339(),
     };
-    ClaudeConfigManager::add_provider(provider)
+    ClaudeConfigManager::add_provider(provider, &secrets)
+}
+
+#[tauri::command]
+fn switch_claude_provider(
+    provider_name: String,
+    secrets: State<SecretStore>,
+) -> Result<(), String> {
+    ClaudeConfigManager::switch_provider(provider_name, &secrets)
+}
+
+#[tauri::command]
+fn remove_claude_provider(
+    provider_name: String,
+    secrets: State<SecretStore>,
+) -> Result<(), String> {
+    ClaudeConfigManager::remove_provider(provider_name, &secrets)
+}
+
+// 密钥存储命令
+
+#[tauri::command]
+fn get_secret_store_status(secrets: State<SecretStore>) -> SecretStoreStatus {
+    secrets.status()
 }
 
 #[tauri::command]
-fn switch_claude_provider(provider_name: String) -> Result<(), String> {
-    ClaudeConfigManager::switch_provider(provider_name)
+fn unlock_secret_store(passphrase: String, secrets: State<SecretStore>) -> Result<(), String> {
+    secrets.unlock(&passphrase)
 }
 
+// passphrase 为空时改回使用本地密钥文件
 #[tauri::command]
-fn remove_claude_provider(provider_name: String) -> Result<(), String> {
-    ClaudeConfigManager::remove_provider(provider_name)
+fn set_secret_store_passphrase(
+    passphrase: Option<String>,
+    secrets: State<SecretStore>,
+) -> Result<(), String> {
+    secrets.set_master_passphrase(passphrase.as_deref())
+}
+
+// 保存机密信息，返回可保存在配置中的引用（secret:<id>）
+#[tauri::command]
+fn store_secret(id: String, value: String, secrets: State<SecretStore>) -> Result<String, String> {
+    secrets.put(&id, &value)
+}
+
+#[tauri::command]
+fn delete_secret(id: String, secrets: State<SecretStore>) -> Result<(), String> {
+    secrets.delete(&id)
+}
+
+// 用户确认后才迁移：把 ~/.claude 中的明文 API Key 移入密钥存储
+#[tauri::command]
+fn migrate_plaintext_secrets(secrets: State<SecretStore>) -> Result<usize, String> {
+    ClaudeConfigManager::migrate_plaintext_secrets(&secrets)
 }
 
 #[tauri::command]
@@ -447,6 +1261,7 @@ pub fn run() {
 
     let terminal_manager = Arc::new(TerminalManager::new());
     let task_manager = Arc::new(Mutex::new(TaskManager::new()));
+    let sftp_manager = Arc::new(SftpManager::new(terminal_manager.host_key_prompts()));
 
     info!("✓ Initialized managers");
 
@@ -456,14 +1271,66 @@ pub fn run() {
         .manage(AppState {
             terminal_manager,
             task_manager,
+            sftp_manager,
+        })
+        .setup(|app| {
+            // 初始化密钥存储；~/.claude 中的明文由用户通过 migrate_plaintext_secrets 主动迁移
+            let config_dir = app.path().app_config_dir()?;
+            app.manage(SecretStore::open(&config_dir)?);
+            Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             start_terminal,
             write_terminal,
+            write_terminal_bytes,
+            paste_terminal,
             resize_terminal,
             close_terminal,
             get_current_dir,
+            get_terminal_process_info,
+            get_terminal_scrollback,
+            list_terminals,
+            attach_terminal,
+            detach_terminal,
+            ack_terminal_output,
+            list_terminal_commands,
+            get_terminal_command_output,
+            get_last_failed_command,
+            set_terminal_scrollback_size,
+            get_terminal_screen,
+            search_terminal,
+            join_broadcast_group,
+            leave_broadcast_group,
+            list_broadcast_groups,
+            broadcast_terminal,
+            start_recording,
+            stop_recording,
+            replay_recording,
             start_ssh_terminal,
+            respond_ssh_hostkey,
+            list_ssh_forwards,
+            add_ssh_forward,
+            remove_ssh_forward,
+            list_ssh_profiles,
+            get_ssh_profile,
+            save_ssh_profile,
+            rename_ssh_profile,
+            delete_ssh_profile,
+            import_ssh_config,
+            sftp_connect,
+            sftp_disconnect,
+            sftp_read_file,
+            sftp_write_file,
+            sftp_list_files,
+            sftp_create_directory,
+            sftp_delete_file,
+            sftp_delete_directory,
+            sftp_copy_file,
+            sftp_move_file,
+            sftp_path_exists,
+            sftp_get_file_metadata,
+            sftp_upload,
+            sftp_download,
             execute_command,
             get_working_directory,
             get_home_directory,
@@ -499,6 +1366,13 @@ pub fn run() {
             switch_claude_provider,
             remove_claude_provider,
             validate_claude_api_key,
+            // 密钥存储命令
+            get_secret_store_status,
+            unlock_secret_store,
+            set_secret_store_passphrase,
+            store_secret,
+            delete_secret,
+            migrate_plaintext_secrets,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,239 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::time::{Duration, Instant};
+
+/// 计算 CPU 占用时两次采样的间隔
+pub const CPU_SAMPLE_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 终端中一个进程的信息
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub ppid: u32,
+    pub name: String,
+    /// 完整命令行，读取不到时（内核线程、僵尸进程）为进程名
+    pub cmdline: String,
+    /// `R` 运行、`S` 睡眠、`T` 停止、`Z` 僵尸等
+    pub state: String,
+    /// 采样期间的 CPU 占用，100 表示占满一个核心
+    pub cpu_percent: f64,
+    /// 常驻内存（RSS）字节数
+    pub memory_bytes: u64,
+}
+
+/// `get_terminal_process_info` 的返回值
+#[derive(Debug, Clone, Serialize)]
+pub struct TerminalProcessInfo {
+    pub session_id: u64,
+    pub shell: ProcessInfo,
+    /// PTY 的前台进程组
+    pub foreground_pgrp: u32,
+    /// 前台进程组不是 shell 本身，即有命令正在运行
+    pub busy: bool,
+    /// 前台进程组中的进程
+    pub foreground: Vec<ProcessInfo>,
+}
+
+/// 正在前台运行的任务，用于关闭终端前的提示
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForegroundJob {
+    pub pgrp: u32,
+    pub cmdline: String,
+}
+
+/// `/proc/<pid>/stat` 中用到的字段
+#[derive(Debug, Clone, PartialEq)]
+struct ProcStat {
+    pid: u32,
+    name: String,
+    state: char,
+    ppid: u32,
+    pgrp: u32,
+    /// 控制终端的前台进程组
+    tpgid: i32,
+    /// utime + stime，单位为时钟滴答
+    cpu_ticks: u64,
+    rss_pages: u64,
+}
+
+/// 读取 shell 及其前台进程组的信息，CPU 占用在 CPU_SAMPLE_INTERVAL 内采样
+///
+/// 依赖 `/proc`，仅在 Linux 上可用
+pub async fn inspect(session_id: u64, shell_pid: u32) -> Result<TerminalProcessInfo, String> {
+    let shell = read_stat(shell_pid).ok_or("无法读取 shell 进程信息（仅支持 Linux）")?;
+    let pgrp = foreground_pgrp(&shell);
+    let busy = pgrp != shell.pgrp;
+
+    let first = sample(shell_pid, pgrp);
+    let started = Instant::now();
+    tokio::time::sleep(CPU_SAMPLE_INTERVAL).await;
+    let second = sample(shell_pid, pgrp);
+    let elapsed = started.elapsed().as_secs_f64();
+
+    let info = |stat: &ProcStat| {
+        let before = first.get(&stat.pid).map_or(stat.cpu_ticks, |s| s.cpu_ticks);
+        let ticks = stat.cpu_ticks.saturating_sub(before) as f64;
+        ProcessInfo {
+            pid: stat.pid,
+            ppid: stat.ppid,
+            name: stat.name.clone(),
+            cmdline: read_cmdline(stat.pid).unwrap_or_else(|| stat.name.clone()),
+            state: stat.state.to_string(),
+            cpu_percent: ticks / clock_ticks_per_second() / elapsed * 100.0,
+            memory_bytes: stat.rss_pages * page_size(),
+        }
+    };
+
+    let shell = second.get(&shell_pid).map(info).ok_or("shell 进程已退出")?;
+    let mut foreground: Vec<ProcessInfo> = second
+        .values()
+        .filter(|stat| stat.pgrp == pgrp)
+        .map(info)
+        .collect();
+    foreground.sort_by_key(|process| process.pid);
+
+    Ok(TerminalProcessInfo {
+        session_id,
+        busy,
+        shell,
+        foreground_pgrp: pgrp,
+        foreground,
+    })
+}
+
+/// shell 正在前台运行的任务；shell 空闲或无法读取 `/proc` 时返回 None
+pub fn foreground_job(shell_pid: u32) -> Option<ForegroundJob> {
+    let shell = read_stat(shell_pid)?;
+    let pgrp = foreground_pgrp(&shell);
+    if pgrp == shell.pgrp {
+        return None;
+    }
+    let leader = read_stat(pgrp);
+    let cmdline = read_cmdline(pgrp)
+        .or_else(|| leader.map(|stat| stat.name))
+        .unwrap_or_default();
+    Some(ForegroundJob { pgrp, cmdline })
+}
+
+// tpgid 为 -1 表示没有控制终端，此时视为 shell 自己在前台
+fn foreground_pgrp(shell: &ProcStat) -> u32 {
+    u32::try_from(shell.tpgid)
+        .ok()
+        .filter(|&pgrp| pgrp > 0)
+        .unwrap_or(shell.pgrp)
+}
+
+// shell 和前台进程组中所有进程的当前状态
+fn sample(shell_pid: u32, pgrp: u32) -> HashMap<u32, ProcStat> {
+    let mut stats: HashMap<u32, ProcStat> = process_ids()
+        .filter_map(read_stat)
+        .filter(|stat| stat.pgrp == pgrp)
+        .map(|stat| (stat.pid, stat))
+        .collect();
+    if let Some(shell) = read_stat(shell_pid) {
+        stats.insert(shell_pid, shell);
+    }
+    stats
+}
+
+fn process_ids() -> impl Iterator<Item = u32> {
+    fs::read_dir("/proc")
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+}
+
+fn read_stat(pid: u32) -> Option<ProcStat> {
+    parse_stat(&fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?)
+}
+
+fn read_cmdline(pid: u32) -> Option<String> {
+    let raw = fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    let args: Vec<_> = raw
+        .split(|&byte| byte == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(String::from_utf8_lossy)
+        .collect();
+    (!args.is_empty()).then(|| args.join(" "))
+}
+
+// 格式：`pid (comm) state ppid pgrp session tty_nr tpgid ...`
+// comm 中可能包含空格和括号，因此从最后一个 `)` 开始解析
+fn parse_stat(content: &str) -> Option<ProcStat> {
+    let open = content.find('(')?;
+    let close = content.rfind(')')?;
+    let pid = content[..open].trim().parse().ok()?;
+    let name = content[open + 1..close].to_string();
+    let fields: Vec<&str> = content[close + 1..].split_whitespace().collect();
+
+    let field = |index: usize| fields.get(index).copied();
+    Some(ProcStat {
+        pid,
+        name,
+        state: field(0)?.chars().next()?,
+        ppid: field(1)?.parse().ok()?,
+        pgrp: field(2)?.parse().ok()?,
+        tpgid: field(5)?.parse().ok()?,
+        cpu_ticks: field(11)?.parse::<u64>().ok()? + field(12)?.parse::<u64>().ok()?,
+        rss_pages: field(21)?.parse().ok()?,
+    })
+}
+
+#[cfg(unix)]
+fn clock_ticks_per_second() -> f64 {
+    match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        ticks if ticks > 0 => ticks as f64,
+        _ => 100.0,
+    }
+}
+
+#[cfg(not(unix))]
+fn clock_ticks_per_second() -> f64 {
+    100.0
+}
+
+#[cfg(unix)]
+fn page_size() -> u64 {
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size > 0 => size as u64,
+        _ => 4096,
+    }
+}
+
+#[cfg(not(unix))]
+fn page_size() -> u64 {
+    4096
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stat() {
+        let content = "4242 (tmux: server (1)) S 1 4242 4242 34816 5000 4194560 \
+                       1200 0 3 0 150 25 0 0 20 0 1 0 99999 12345678 512 \
+                       18446744073709551615 1 1 0 0 0 0 0 0 0 0 0 0 17 3 0 0 0 0 0";
+        let stat = parse_stat(content).unwrap();
+        assert_eq!(stat.pid, 4242);
+        assert_eq!(stat.name, "tmux: server (1)");
+        assert_eq!(stat.state, 'S');
+        assert_eq!((stat.ppid, stat.pgrp, stat.tpgid), (1, 4242, 5000));
+        assert_eq!(stat.cpu_ticks, 175);
+        assert_eq!(stat.rss_pages, 512);
+
+        assert!(parse_stat("garbage").is_none());
+        assert!(parse_stat("1 (init) S 0").is_none());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_read_current_process() {
+        let pid = std::process::id();
+        let stat = read_stat(pid).unwrap();
+        assert_eq!(stat.pid, pid);
+        assert!(stat.rss_pages > 0);
+        assert!(read_cmdline(pid).is_some());
+    }
+}
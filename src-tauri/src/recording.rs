@@ -0,0 +1,272 @@
+use crate::commands::filesystem::is_sensitive_path;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// asciicast v2 文件头
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CastHeader {
+    pub version: u32,
+    pub width: u16,
+    pub height: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+}
+
+/// asciicast v2 事件：`[时间, 类型, 数据]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CastEvent {
+    /// 距离录制开始的秒数
+    pub time: f64,
+    /// `o` 输出、`i` 输入、`r` 窗口大小变化（数据为 `列x行`）
+    pub kind: String,
+    pub data: String,
+}
+
+/// 将终端会话录制为 asciicast v2（.cast）文件
+pub struct AsciicastRecorder {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    started: Instant,
+    record_input: bool,
+}
+
+impl AsciicastRecorder {
+    /// 创建录制文件；拒绝敏感路径，文件已存在时除非 overwrite 为 true 否则报错
+    pub fn create(
+        path: &Path,
+        cols: u16,
+        rows: u16,
+        title: Option<String>,
+        shell: &str,
+        record_input: bool,
+        overwrite: bool,
+    ) -> Result<Self, String> {
+        if is_sensitive_path(&path.to_string_lossy()) {
+            return Err("拒绝访问：无法写入敏感系统文件".to_string());
+        }
+        let parent = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        fs::create_dir_all(parent).map_err(|e| format!("无法创建录制目录: {}", e))?;
+
+        // 规范化父目录后再检查一次，防止通过 .. 或符号链接绕过
+        let file_name = path.file_name().ok_or("无效的录制文件名")?;
+        let path = parent
+            .canonicalize()
+            .map_err(|e| format!("无法解析录制目录: {}", e))?
+            .join(file_name);
+        if is_sensitive_path(&path.to_string_lossy()) {
+            return Err("拒绝访问：无法写入敏感系统文件".to_string());
+        }
+
+        let mut options = OpenOptions::new();
+        options.write(true);
+        if overwrite {
+            options.create(true).truncate(true);
+        } else {
+            options.create_new(true);
+        }
+        let file = options.open(&path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::AlreadyExists => format!("录制文件已存在: {}", path.display()),
+            _ => format!("无法创建录制文件: {}", e),
+        })?;
+
+        let header = CastHeader {
+            version: 2,
+            width: cols,
+            height: rows,
+            timestamp: Some(chrono::Utc::now().timestamp()),
+            title,
+            env: HashMap::from([
+                ("SHELL".to_string(), shell.to_string()),
+                ("TERM".to_string(), "xterm-256color".to_string()),
+            ]),
+        };
+
+        let mut recorder = Self {
+            path,
+            writer: BufWriter::new(file),
+            started: Instant::now(),
+            record_input,
+        };
+        let header =
+            serde_json::to_string(&header).map_err(|e| format!("无法序列化录制文件头: {}", e))?;
+        recorder.write_line(&header)?;
+        Ok(recorder)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn output(&mut self, data: &str) -> Result<(), String> {
+        self.event("o", data)
+    }
+
+    /// 记录输入；创建时未开启输入录制（避免记录密码等敏感输入）则忽略
+    pub fn input(&mut self, data: &str) -> Result<(), String> {
+        if !self.record_input {
+            return Ok(());
+        }
+        self.event("i", data)
+    }
+
+    pub fn resize(&mut self, cols: u16, rows: u16) -> Result<(), String> {
+        self.event("r", &format!("{}x{}", cols, rows))
+    }
+
+    /// 写入剩余数据并关闭文件
+    pub fn finish(mut self) -> Result<PathBuf, String> {
+        self.writer
+            .flush()
+            .map_err(|e| format!("无法写入录制文件: {}", e))?;
+        Ok(self.path)
+    }
+
+    fn event(&mut self, kind: &str, data: &str) -> Result<(), String> {
+        let time = self.started.elapsed().as_secs_f64();
+        let line = serde_json::to_string(&(round_time(time), kind, data))
+            .map_err(|e| format!("无法序列化录制事件: {}", e))?;
+        self.write_line(&line)
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.writer, "{}", line).map_err(|e| format!("无法写入录制文件: {}", e))
+    }
+}
+
+// asciicast 时间保留到微秒
+fn round_time(time: f64) -> f64 {
+    (time * 1_000_000.0).round() / 1_000_000.0
+}
+
+/// 读取 .cast 文件，返回文件头和所有事件
+pub fn read_cast(path: &Path) -> Result<(CastHeader, Vec<CastEvent>), String> {
+    let file = File::open(path).map_err(|e| format!("无法打开录制文件: {}", e))?;
+    let mut lines = BufReader::new(file).lines();
+
+    let header = lines
+        .next()
+        .ok_or("录制文件为空")?
+        .map_err(|e| format!("无法读取录制文件: {}", e))?;
+    let header: CastHeader =
+        serde_json::from_str(&header).map_err(|e| format!("无法解析录制文件头: {}", e))?;
+    if header.version != 2 {
+        return Err(format!("不支持的 asciicast 版本: {}", header.version));
+    }
+
+    let mut events = Vec::new();
+    for (index, line) in lines.enumerate() {
+        let line = line.map_err(|e| format!("无法读取录制文件: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event =
+            parse_event(&line).ok_or_else(|| format!("录制文件第 {} 行格式错误", index + 2))?;
+        events.push(event);
+    }
+    Ok((header, events))
+}
+
+fn parse_event(line: &str) -> Option<CastEvent> {
+    let value: Value = serde_json::from_str(line).ok()?;
+    let array = value.as_array()?;
+    match array.as_slice() {
+        [time, kind, data] => Some(CastEvent {
+            time: time.as_f64()?,
+            kind: kind.as_str()?.to_string(),
+            data: data.as_str()?.to_string(),
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_read_cast() {
+        let path = std::env::temp_dir().join(format!(
+            "huaan_recording_{}/session.cast",
+            std::process::id()
+        ));
+
+        let mut recorder = AsciicastRecorder::create(
+            &path,
+            80,
+            24,
+            Some("demo".to_string()),
+            "/bin/zsh",
+            false,
+            false,
+        )
+        .unwrap();
+        recorder.output("$ ls\r\n").unwrap();
+        recorder.input("secret\r").unwrap();
+        recorder.resize(120, 40).unwrap();
+        recorder.output("中文\x1b[0m").unwrap();
+        let path = recorder.finish().unwrap();
+
+        let (header, events) = read_cast(&path).unwrap();
+        assert_eq!(header.version, 2);
+        assert_eq!((header.width, header.height), (80, 24));
+        assert_eq!(header.title.as_deref(), Some("demo"));
+        assert_eq!(
+            header.env.get("SHELL").map(String::as_str),
+            Some("/bin/zsh")
+        );
+
+        // 未开启输入录制时不记录输入
+        let kinds: Vec<_> = events.iter().map(|e| e.kind.as_str()).collect();
+        assert_eq!(kinds, vec!["o", "r", "o"]);
+        assert_eq!(events[1].data, "120x40");
+        assert_eq!(events[2].data, "中文\x1b[0m");
+        assert!(events.windows(2).all(|w| w[0].time <= w[1].time));
+
+        // 已有文件默认不覆盖
+        assert!(
+            AsciicastRecorder::create(&path, 80, 24, None, "/bin/zsh", false, false)
+                .err()
+                .unwrap()
+                .contains("已存在")
+        );
+        assert!(AsciicastRecorder::create(&path, 80, 24, None, "/bin/zsh", false, true).is_ok());
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_create_rejects_sensitive_paths() {
+        for path in ["/etc/passwd", "/tmp/../etc/ssh/sshd_config"] {
+            let result =
+                AsciicastRecorder::create(Path::new(path), 80, 24, None, "/bin/sh", false, true);
+            assert!(result.err().unwrap().contains("拒绝访问"));
+        }
+    }
+
+    #[test]
+    fn test_read_cast_rejects_bad_files() {
+        let path = std::env::temp_dir().join(format!("huaan_bad_{}.cast", std::process::id()));
+        fs::write(&path, "{\"version\":1,\"width\":80,\"height\":24}\n").unwrap();
+        assert!(read_cast(&path).is_err());
+
+        fs::write(
+            &path,
+            "{\"version\":2,\"width\":80,\"height\":24}\n[0.5, \"o\", \"ok\"]\n[1.0, \"o\"]\n",
+        )
+        .unwrap();
+        assert!(read_cast(&path).unwrap_err().contains("第 3 行"));
+        let _ = fs::remove_file(&path);
+    }
+}
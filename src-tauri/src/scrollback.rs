@@ -0,0 +1,145 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+
+/// 默认每个会话保留 1 MiB 的输出历史
+pub const DEFAULT_SCROLLBACK_BYTES: usize = 1024 * 1024;
+
+/// 终端输出的环形缓冲区
+///
+/// 偏移量是会话启动以来输出的总字节数，旧数据被丢弃后偏移量仍然连续，
+/// 客户端可以用上次收到的 `end_offset` 无缝续传
+pub struct Scrollback {
+    buf: VecDeque<u8>,
+    capacity: usize,
+    /// 缓冲区中第一个字节的偏移量
+    start_offset: u64,
+}
+
+/// `get_terminal_scrollback` 的返回值
+#[derive(Debug, Clone, Serialize)]
+pub struct ScrollbackChunk {
+    pub data: String,
+    /// data 中第一个字节的偏移量
+    pub start_offset: u64,
+    /// 下一个输出字节的偏移量
+    pub end_offset: u64,
+    /// 请求的偏移量之后有数据已被丢弃
+    pub truncated: bool,
+}
+
+impl Scrollback {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buf: VecDeque::with_capacity(capacity.min(64 * 1024)),
+            capacity,
+            start_offset: 0,
+        }
+    }
+
+    pub fn end_offset(&self) -> u64 {
+        self.start_offset + self.buf.len() as u64
+    }
+
+    /// 追加输出，返回这段数据的起始偏移量
+    pub fn push(&mut self, data: &[u8]) -> u64 {
+        let offset = self.end_offset();
+        self.buf.extend(data);
+        self.trim();
+        offset
+    }
+
+    /// 调整容量，超出部分从最旧的数据开始丢弃
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.trim();
+    }
+
+    /// 返回从 `since` 开始仍保留在缓冲区中的输出
+    pub fn since(&self, since: u64) -> ScrollbackChunk {
+        self.range(since, self.end_offset())
+    }
+
+    /// 返回 `[start, end)` 范围内仍保留在缓冲区中的输出
+    pub fn range(&self, since: u64, end: u64) -> ScrollbackChunk {
+        let end_offset = end.min(self.end_offset());
+        let since = since.min(end_offset);
+        let truncated = since < self.start_offset;
+        let mut skip = since.saturating_sub(self.start_offset) as usize;
+
+        // 被截断时从完整的 UTF-8 字符开始，避免开头出现乱码
+        if truncated {
+            while skip < self.buf.len() && is_utf8_continuation(self.buf[skip]) {
+                skip += 1;
+            }
+        }
+
+        let end = end_offset.saturating_sub(self.start_offset) as usize;
+        let bytes: Vec<u8> = self.buf.range(skip.min(end)..end).copied().collect();
+        ScrollbackChunk {
+            data: String::from_utf8_lossy(&bytes).into_owned(),
+            start_offset: self.start_offset + skip.min(end) as u64,
+            end_offset,
+            truncated,
+        }
+    }
+
+    /// 缓冲区中的全部原始字节及其起始偏移量
+    pub fn snapshot(&self) -> (u64, Vec<u8>) {
+        (self.start_offset, self.buf.iter().copied().collect())
+    }
+
+    fn trim(&mut self) {
+        if self.buf.len() > self.capacity {
+            let excess = self.buf.len() - self.capacity;
+            self.buf.drain(..excess);
+            self.start_offset += excess as u64;
+        }
+    }
+}
+
+fn is_utf8_continuation(byte: u8) -> bool {
+    byte & 0xC0 == 0x80
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offsets_continue_after_wrap() {
+        let mut scrollback = Scrollback::new(8);
+        assert_eq!(scrollback.push(b"hello "), 0);
+        assert_eq!(scrollback.push(b"world"), 6);
+        assert_eq!(scrollback.end_offset(), 11);
+
+        let all = scrollback.since(0);
+        assert_eq!(all.data, "lo world");
+        assert_eq!(all.start_offset, 3);
+        assert!(all.truncated);
+
+        let tail = scrollback.since(6);
+        assert_eq!(tail.data, "world");
+        assert!(!tail.truncated);
+
+        let middle = scrollback.range(4, 9);
+        assert_eq!(middle.data, "o wor");
+        assert_eq!((middle.start_offset, middle.end_offset), (4, 9));
+
+        let empty = scrollback.since(100);
+        assert_eq!(empty.data, "");
+        assert_eq!(empty.start_offset, 11);
+    }
+
+    #[test]
+    fn test_truncated_start_skips_partial_utf8() {
+        let mut scrollback = Scrollback::new(3);
+        // "中" 占 3 个字节，截断后只剩后两个字节
+        scrollback.push("a中b".as_bytes());
+        let chunk = scrollback.since(0);
+        assert_eq!(chunk.data, "b");
+        assert_eq!(chunk.start_offset, 4);
+
+        scrollback.set_capacity(1);
+        assert_eq!(scrollback.since(0).data, "b");
+    }
+}
@@ -0,0 +1,217 @@
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+
+/// 一次搜索最多返回的匹配数
+pub const MAX_SEARCH_MATCHES: usize = 1000;
+
+/// 终端输出中的一处匹配
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchMatch {
+    pub session_id: u64,
+    /// 所在行，从 scrollback 中保留的第一行开始计数
+    pub line: usize,
+    /// 行内起始列（按字符计）
+    pub column: usize,
+    /// 匹配在输出流中的起始偏移量，与 scrollback 的偏移量一致
+    pub offset: u64,
+    /// 匹配最后一个字节之后的偏移量
+    pub end_offset: u64,
+    /// 去掉转义序列后的整行文本
+    pub line_text: String,
+}
+
+/// 编译好的搜索条件
+pub struct SearchQuery {
+    regex: Regex,
+}
+
+impl SearchQuery {
+    /// `regex` 为 false 时按普通文本匹配
+    pub fn new(pattern: &str, regex: bool, case_sensitive: bool) -> Result<Self, String> {
+        if pattern.is_empty() {
+            return Err("搜索内容不能为空".to_string());
+        }
+        let pattern = if regex {
+            pattern.to_string()
+        } else {
+            regex::escape(pattern)
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!case_sensitive)
+            .build()
+            .map_err(|e| format!("无效的正则表达式: {}", e))?;
+        Ok(Self { regex })
+    }
+
+    /// 在一段原始输出中逐行搜索，`start_offset` 为 data 第一个字节的偏移量
+    pub fn search(
+        &self,
+        session_id: u64,
+        start_offset: u64,
+        data: &[u8],
+        limit: usize,
+    ) -> Vec<SearchMatch> {
+        let (text, positions) = strip_escapes(data);
+        let mut matches = Vec::new();
+        let mut line_start = 0;
+
+        for (line_no, line) in text.split('\n').enumerate() {
+            for found in self.regex.find_iter(line) {
+                if matches.len() >= limit {
+                    return matches;
+                }
+                if found.is_empty() {
+                    continue;
+                }
+                let first = positions[line_start + found.start()];
+                let last = positions[line_start + found.end() - 1];
+                matches.push(SearchMatch {
+                    session_id,
+                    line: line_no,
+                    column: line[..found.start()].chars().count(),
+                    offset: start_offset + first as u64,
+                    end_offset: start_offset + last as u64 + 1,
+                    line_text: line.to_string(),
+                });
+            }
+            line_start += line.len() + 1;
+        }
+        matches
+    }
+}
+
+/// 去掉转义序列和控制字符（保留换行和制表符）
+///
+/// 返回纯文本，以及文本中每个字节在原始数据中的位置
+fn strip_escapes(data: &[u8]) -> (String, Vec<usize>) {
+    let mut text = String::with_capacity(data.len());
+    let mut positions = Vec::with_capacity(data.len());
+    let mut i = 0;
+
+    while i < data.len() {
+        match data[i] {
+            0x1b => i = skip_escape(data, i),
+            b'\n' | b'\t' => {
+                text.push(data[i] as char);
+                positions.push(i);
+                i += 1;
+            }
+            byte if byte < 0x20 || byte == 0x7f => i += 1,
+            byte if byte < 0x80 => {
+                text.push(byte as char);
+                positions.push(i);
+                i += 1;
+            }
+            byte => {
+                let len = utf8_len(byte);
+                let ch = data
+                    .get(i..i + len)
+                    .and_then(|bytes| std::str::from_utf8(bytes).ok());
+                match ch {
+                    Some(ch) => {
+                        text.push_str(ch);
+                        positions.extend(i..i + len);
+                        i += len;
+                    }
+                    // scrollback 开头被截断的字符等无效数据直接跳过
+                    None => i += 1,
+                }
+            }
+        }
+    }
+    (text, positions)
+}
+
+// 跳过从 start 开始的转义序列，返回序列之后的位置
+fn skip_escape(data: &[u8], start: usize) -> usize {
+    let Some(&kind) = data.get(start + 1) else {
+        return data.len();
+    };
+    match kind {
+        // CSI：参数和中间字节之后以 0x40..=0x7e 结束
+        b'[' => data[start + 2..]
+            .iter()
+            .position(|byte| (0x40..=0x7e).contains(byte))
+            .map_or(data.len(), |end| start + 2 + end + 1),
+        // OSC、DCS、SOS、PM、APC：以 BEL 或 ST 结束
+        b']' | b'P' | b'X' | b'^' | b'_' => {
+            let body = &data[start + 2..];
+            match body.iter().position(|&byte| byte == 0x07 || byte == 0x1b) {
+                Some(end) if body[end] == 0x07 => start + 2 + end + 1,
+                Some(end) if body.get(end + 1) == Some(&b'\\') => start + 2 + end + 2,
+                // 被下一个 ESC 打断
+                Some(end) => start + 2 + end,
+                None => data.len(),
+            }
+        }
+        // 带中间字节的序列，例如 `ESC ( B`
+        0x20..=0x2f => data[start + 2..]
+            .iter()
+            .position(|byte| !(0x20..=0x2f).contains(byte))
+            .map_or(data.len(), |end| start + 2 + end + 1),
+        _ => start + 2,
+    }
+}
+
+fn utf8_len(first: u8) -> usize {
+    match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_escapes() {
+        let data = "\x1b]0;title\x07\x1b[1;31merror\x1b[0m: 中文\r\n\x1b(Bok\x1b[?2004h\x08!";
+        let (text, positions) = strip_escapes(data.as_bytes());
+        assert_eq!(text, "error: 中文\nok!");
+        assert_eq!(positions.len(), text.len());
+        assert_eq!(&data[positions[0]..positions[0] + 5], "error");
+    }
+
+    #[test]
+    fn test_search_offsets_and_columns() {
+        let data = "$ make\r\n\x1b[31mError\x1b[0m: 找不到 error.h\r\nok\r\n";
+        let query = SearchQuery::new("error", false, false).unwrap();
+        let matches = query.search(3, 100, data.as_bytes(), MAX_SEARCH_MATCHES);
+        assert_eq!(matches.len(), 2);
+
+        // 匹配跨越颜色序列时，偏移量覆盖原始数据中的整个范围
+        let first = &matches[0];
+        assert_eq!((first.session_id, first.line, first.column), (3, 1, 0));
+        assert_eq!(first.line_text, "Error: 找不到 error.h");
+        let raw = &data[(first.offset - 100) as usize..(first.end_offset - 100) as usize];
+        assert_eq!(raw, "Error");
+
+        assert_eq!((matches[1].line, matches[1].column), (1, 11));
+
+        let query = SearchQuery::new("error", false, true).unwrap();
+        assert_eq!(query.search(3, 0, data.as_bytes(), 10).len(), 1);
+        assert_eq!(query.search(3, 0, data.as_bytes(), 0).len(), 0);
+    }
+
+    #[test]
+    fn test_search_regex() {
+        let data = b"exit 0\nexit 127\nexit\n";
+        let query = SearchQuery::new(r"exit \d+", true, true).unwrap();
+        let lines: Vec<_> = query
+            .search(1, 0, data, MAX_SEARCH_MATCHES)
+            .iter()
+            .map(|m| m.line)
+            .collect();
+        assert_eq!(lines, vec![0, 1]);
+
+        // 普通文本模式下正则符号按字面匹配
+        let query = SearchQuery::new(r"\d+", false, true).unwrap();
+        assert!(query.search(1, 0, data, MAX_SEARCH_MATCHES).is_empty());
+
+        assert!(SearchQuery::new("(", true, true).is_err());
+        assert!(SearchQuery::new("", false, true).is_err());
+    }
+}
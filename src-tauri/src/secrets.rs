@@ -0,0 +1,513 @@
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// 密钥引用前缀，配置文件中只保存 `secret:<id>` 而不是明文
+pub const SECRET_REF_PREFIX: &str = "secret:";
+
+const KEYRING_SERVICE: &str = "huaan-command";
+const SECRETS_FILE: &str = "secrets.json";
+const KEY_FILE: &str = "secrets.key";
+const VERIFIER_ID: &str = "__verifier__";
+const VERIFIER_PLAINTEXT: &[u8] = b"huaan-command-secrets";
+
+/// 生成密钥引用
+pub fn secret_ref(id: &str) -> String {
+    format!("{}{}", SECRET_REF_PREFIX, id)
+}
+
+/// 如果值是密钥引用，返回其中的 id
+pub fn parse_secret_ref(value: &str) -> Option<&str> {
+    value
+        .strip_prefix(SECRET_REF_PREFIX)
+        .filter(|id| !id.is_empty())
+}
+
+/// 密钥存储状态（返回给前端）
+#[derive(Debug, Clone, Serialize)]
+pub struct SecretStoreStatus {
+    /// "keyring" 或 "file"
+    pub backend: String,
+    /// 文件存储是否使用主密码加密
+    pub uses_passphrase: bool,
+    /// 文件存储是否等待主密码解锁
+    pub locked: bool,
+}
+
+/// 机密信息存储：优先使用系统钥匙串，不可用时使用本地加密文件
+pub struct SecretStore {
+    backend: Mutex<Backend>,
+}
+
+enum Backend {
+    Keyring,
+    File(EncryptedFile),
+}
+
+impl SecretStore {
+    /// 打开存储；系统钥匙串不可用时使用 `config_dir/secrets.json`
+    pub fn open(config_dir: &Path) -> Result<Self, String> {
+        let backend = if keyring_available() {
+            Backend::Keyring
+        } else {
+            Backend::File(EncryptedFile::open(config_dir)?)
+        };
+        Ok(Self {
+            backend: Mutex::new(backend),
+        })
+    }
+
+    /// 始终使用加密文件存储（忽略系统钥匙串）
+    #[cfg(test)]
+    pub fn open_file(config_dir: &Path) -> Result<Self, String> {
+        Ok(Self {
+            backend: Mutex::new(Backend::File(EncryptedFile::open(config_dir)?)),
+        })
+    }
+
+    /// 保存机密信息，返回可写入配置文件的引用
+    pub fn put(&self, id: &str, value: &str) -> Result<String, String> {
+        if id.is_empty() {
+            return Err("密钥 id 不能为空".to_string());
+        }
+        match &mut *self.backend.lock().unwrap() {
+            Backend::Keyring => keyring_entry(id)?
+                .set_password(value)
+                .map_err(|e| format!("无法写入系统钥匙串: {}", e))?,
+            Backend::File(file) => file.put(id, value)?,
+        }
+        Ok(secret_ref(id))
+    }
+
+    pub fn get(&self, id: &str) -> Result<String, String> {
+        match &*self.backend.lock().unwrap() {
+            Backend::Keyring => keyring_entry(id)?
+                .get_password()
+                .map_err(|e| format!("无法读取密钥 '{}': {}", id, e)),
+            Backend::File(file) => file.get(id),
+        }
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), String> {
+        match &mut *self.backend.lock().unwrap() {
+            Backend::Keyring => match keyring_entry(id)?.delete_credential() {
+                Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+                Err(e) => Err(format!("无法删除密钥 '{}': {}", id, e)),
+            },
+            Backend::File(file) => file.delete(id),
+        }
+    }
+
+    /// 将引用解析为明文；不是引用的值原样返回（兼容旧配置）
+    pub fn resolve(&self, value: &str) -> Result<String, String> {
+        match parse_secret_ref(value) {
+            Some(id) => self.get(id),
+            None => Ok(value.to_string()),
+        }
+    }
+
+    pub fn status(&self) -> SecretStoreStatus {
+        match &*self.backend.lock().unwrap() {
+            Backend::Keyring => SecretStoreStatus {
+                backend: "keyring".to_string(),
+                uses_passphrase: false,
+                locked: false,
+            },
+            Backend::File(file) => SecretStoreStatus {
+                backend: "file".to_string(),
+                uses_passphrase: file.data.kdf_salt.is_some(),
+                locked: file.key.is_none(),
+            },
+        }
+    }
+
+    /// 使用主密码解锁文件存储
+    pub fn unlock(&self, passphrase: &str) -> Result<(), String> {
+        match &mut *self.backend.lock().unwrap() {
+            Backend::Keyring => Ok(()),
+            Backend::File(file) => file.unlock(passphrase),
+        }
+    }
+
+    /// 设置（Some）或移除（None）文件存储的主密码，已有密钥会重新加密
+    pub fn set_master_passphrase(&self, passphrase: Option<&str>) -> Result<(), String> {
+        match &mut *self.backend.lock().unwrap() {
+            Backend::Keyring => Err("当前使用系统钥匙串，无需设置主密码".to_string()),
+            Backend::File(file) => file.set_master_passphrase(passphrase),
+        }
+    }
+}
+
+fn keyring_entry(id: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, id).map_err(|e| format!("无法访问系统钥匙串: {}", e))
+}
+
+// 探测系统钥匙串是否可用：读取一个不存在的条目应返回 NoEntry
+fn keyring_available() -> bool {
+    match keyring::Entry::new(KEYRING_SERVICE, "__probe__") {
+        Ok(entry) => matches!(entry.get_password(), Ok(_) | Err(keyring::Error::NoEntry)),
+        Err(_) => false,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EncryptedValue {
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SecretsData {
+    version: u32,
+    /// 设置了主密码时的 Argon2 盐；为空表示使用本地密钥文件
+    #[serde(default)]
+    kdf_salt: Option<String>,
+    /// 用于校验主密码是否正确
+    #[serde(default)]
+    verifier: Option<EncryptedValue>,
+    #[serde(default)]
+    entries: BTreeMap<String, EncryptedValue>,
+}
+
+/// ChaCha20-Poly1305 加密的密钥文件，密钥来自主密码（Argon2）或本地密钥文件
+struct EncryptedFile {
+    path: PathBuf,
+    key_path: PathBuf,
+    data: SecretsData,
+    key: Option<[u8; 32]>,
+}
+
+impl EncryptedFile {
+    fn open(config_dir: &Path) -> Result<Self, String> {
+        fs::create_dir_all(config_dir).map_err(|e| format!("无法创建配置目录: {}", e))?;
+        let path = config_dir.join(SECRETS_FILE);
+        let key_path = config_dir.join(KEY_FILE);
+
+        let data = if path.exists() {
+            let content =
+                fs::read_to_string(&path).map_err(|e| format!("无法读取密钥存储: {}", e))?;
+            serde_json::from_str(&content).map_err(|e| format!("无法解析密钥存储: {}", e))?
+        } else {
+            SecretsData {
+                version: 1,
+                ..Default::default()
+            }
+        };
+
+        let mut file = Self {
+            path,
+            key_path,
+            data,
+            key: None,
+        };
+        // 未设置主密码时使用本地密钥文件，立即可用
+        if file.data.kdf_salt.is_none() {
+            file.key = Some(file.load_or_create_key_file()?);
+        }
+        Ok(file)
+    }
+
+    fn load_or_create_key_file(&self) -> Result<[u8; 32], String> {
+        if self.key_path.exists() {
+            let bytes =
+                fs::read(&self.key_path).map_err(|e| format!("无法读取本地密钥文件: {}", e))?;
+            return bytes
+                .try_into()
+                .map_err(|_| "本地密钥文件已损坏".to_string());
+        }
+
+        let mut key = [0u8; 32];
+        OsRng.fill_bytes(&mut key);
+        write_private_file(&self.key_path, &key)?;
+        Ok(key)
+    }
+
+    fn unlock(&mut self, passphrase: &str) -> Result<(), String> {
+        let salt = self
+            .data
+            .kdf_salt
+            .as_deref()
+            .ok_or("密钥存储未设置主密码")?;
+        let key = derive_key(passphrase, salt)?;
+        if let Some(verifier) = &self.data.verifier {
+            decrypt(&key, VERIFIER_ID, verifier).map_err(|_| "主密码错误".to_string())?;
+        }
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn set_master_passphrase(&mut self, passphrase: Option<&str>) -> Result<(), String> {
+        let old_key = self.key.ok_or("密钥存储已锁定，请先解锁")?;
+
+        // 用旧密钥解密全部条目
+        let mut plaintexts = BTreeMap::new();
+        for (id, value) in &self.data.entries {
+            plaintexts.insert(id.clone(), decrypt(&old_key, id, value)?);
+        }
+
+        let (new_key, kdf_salt, verifier) = match passphrase {
+            Some(passphrase) if !passphrase.is_empty() => {
+                let mut salt = [0u8; 16];
+                OsRng.fill_bytes(&mut salt);
+                let salt = BASE64.encode(salt);
+                let key = derive_key(passphrase, &salt)?;
+                let verifier = encrypt(&key, VERIFIER_ID, VERIFIER_PLAINTEXT)?;
+                (key, Some(salt), Some(verifier))
+            }
+            // 本来就在使用本地密钥文件，沿用原密钥
+            _ if self.data.kdf_salt.is_none() => (old_key, None, None),
+            _ => {
+                // 先落盘新密钥文件；此时存储仍使用主密码，不会读取它
+                let mut key = [0u8; 32];
+                OsRng.fill_bytes(&mut key);
+                write_private_file(&self.key_path, &key)?;
+                (key, None, None)
+            }
+        };
+
+        let data = SecretsData {
+            version: self.data.version,
+            kdf_salt,
+            verifier,
+            entries: plaintexts
+                .iter()
+                .map(|(id, value)| Ok((id.clone(), encrypt(&new_key, id, value.as_bytes())?)))
+                .collect::<Result<_, String>>()?,
+        };
+        // 新存储保存成功后才替换内存状态，失败时旧存储和旧密钥都保持可用
+        save_data(&self.path, &data)?;
+        self.data = data;
+        self.key = Some(new_key);
+
+        // 改用主密码后不再需要本地密钥文件
+        if self.data.kdf_salt.is_some() {
+            let _ = fs::remove_file(&self.key_path);
+        }
+        Ok(())
+    }
+
+    fn put(&mut self, id: &str, value: &str) -> Result<(), String> {
+        let key = self.key.ok_or("密钥存储已锁定，请先输入主密码")?;
+        let encrypted = encrypt(&key, id, value.as_bytes())?;
+        self.data.entries.insert(id.to_string(), encrypted);
+        self.save()
+    }
+
+    fn get(&self, id: &str) -> Result<String, String> {
+        let key = self.key.ok_or("密钥存储已锁定，请先输入主密码")?;
+        let value = self
+            .data
+            .entries
+            .get(id)
+            .ok_or_else(|| format!("密钥 '{}' 不存在", id))?;
+        decrypt(&key, id, value)
+    }
+
+    fn delete(&mut self, id: &str) -> Result<(), String> {
+        if self.data.entries.remove(id).is_some() {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), String> {
+        save_data(&self.path, &self.data)
+    }
+}
+
+fn save_data(path: &Path, data: &SecretsData) -> Result<(), String> {
+    let json =
+        serde_json::to_string_pretty(data).map_err(|e| format!("无法序列化密钥存储: {}", e))?;
+    write_private_file(path, json.as_bytes())
+}
+
+fn derive_key(passphrase: &str, salt: &str) -> Result<[u8; 32], String> {
+    let salt = BASE64
+        .decode(salt)
+        .map_err(|e| format!("密钥存储盐值无效: {}", e))?;
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|e| format!("无法派生密钥: {}", e))?;
+    Ok(key)
+}
+
+// id 作为附加数据参与认证，防止密文被挪用到其他条目
+fn encrypt(key: &[u8; 32], id: &str, plaintext: &[u8]) -> Result<EncryptedValue, String> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce);
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad: id.as_bytes(),
+            },
+        )
+        .map_err(|_| "加密失败".to_string())?;
+    Ok(EncryptedValue {
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    })
+}
+
+fn decrypt(key: &[u8; 32], id: &str, value: &EncryptedValue) -> Result<String, String> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let nonce = BASE64
+        .decode(&value.nonce)
+        .map_err(|_| format!("密钥 '{}' 已损坏", id))?;
+    let ciphertext = BASE64
+        .decode(&value.ciphertext)
+        .map_err(|_| format!("密钥 '{}' 已损坏", id))?;
+    if nonce.len() != 12 {
+        return Err(format!("密钥 '{}' 已损坏", id));
+    }
+    let plaintext = cipher
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad: id.as_bytes(),
+            },
+        )
+        .map_err(|_| format!("无法解密密钥 '{}'", id))?;
+    String::from_utf8(plaintext).map_err(|_| format!("密钥 '{}' 不是有效的 UTF-8", id))
+}
+
+// 写入仅当前用户可读写的文件；新文件创建时即为 0600，不存在可被其他用户读取的窗口。
+// 先写入同目录的临时文件并 fsync，再 rename 覆盖目标，中途崩溃不会留下截断的文件
+fn write_private_file(path: &Path, content: &[u8]) -> Result<(), String> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(&tmp_path)
+        .map_err(|e| format!("无法写入 {}: {}", tmp_path.display(), e))?;
+
+    // mode 只对新建的文件生效，残留的临时文件仍需收紧权限
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("无法设置 {} 的权限: {}", tmp_path.display(), e))?;
+    }
+    file.write_all(content)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| format!("无法写入 {}: {}", tmp_path.display(), e))?;
+    drop(file);
+
+    fs::rename(&tmp_path, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp_path);
+        format!("无法写入 {}: {}", path.display(), e)
+    })?;
+
+    // 同步目录项，确保 rename 本身已落盘
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        if let Ok(dir) = fs::File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("huaan_secrets_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_secret_ref() {
+        assert_eq!(secret_ref("claude/work"), "secret:claude/work");
+        assert_eq!(parse_secret_ref("secret:claude/work"), Some("claude/work"));
+        assert_eq!(parse_secret_ref("secret:"), None);
+        assert_eq!(parse_secret_ref("sk-plaintext"), None);
+    }
+
+    #[test]
+    fn test_file_store_with_key_file() {
+        let dir = temp_dir("keyfile");
+        let store = SecretStore::open_file(&dir).unwrap();
+        let reference = store.put("ssh/1", "hunter2").unwrap();
+        assert_eq!(reference, "secret:ssh/1");
+        assert_eq!(store.resolve(&reference).unwrap(), "hunter2");
+        assert_eq!(store.resolve("plain").unwrap(), "plain");
+
+        // 明文不应出现在磁盘上
+        let on_disk = fs::read_to_string(dir.join(SECRETS_FILE)).unwrap();
+        assert!(!on_disk.contains("hunter2"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for file in [SECRETS_FILE, KEY_FILE] {
+                let mode = fs::metadata(dir.join(file)).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o600);
+            }
+        }
+
+        // 重新打开后仍可读取
+        let reopened = SecretStore::open_file(&dir).unwrap();
+        assert_eq!(reopened.get("ssh/1").unwrap(), "hunter2");
+
+        reopened.delete("ssh/1").unwrap();
+        assert!(reopened.get("ssh/1").is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_file_store_with_passphrase() {
+        let dir = temp_dir("passphrase");
+        let store = SecretStore::open_file(&dir).unwrap();
+        store.put("claude/work", "sk-test-123").unwrap();
+        store.set_master_passphrase(Some("correct horse")).unwrap();
+        assert!(!dir.join(KEY_FILE).exists());
+
+        let reopened = SecretStore::open_file(&dir).unwrap();
+        assert!(reopened.status().locked);
+        assert!(reopened.get("claude/work").is_err());
+        assert!(reopened.unlock("wrong").is_err());
+        reopened.unlock("correct horse").unwrap();
+        assert_eq!(reopened.get("claude/work").unwrap(), "sk-test-123");
+
+        // 移除主密码后回到本地密钥文件
+        reopened.set_master_passphrase(None).unwrap();
+        let reopened = SecretStore::open_file(&dir).unwrap();
+        assert!(!reopened.status().locked);
+        assert_eq!(reopened.get("claude/work").unwrap(), "sk-test-123");
+
+        // 已经使用本地密钥文件时不重新生成密钥
+        let key = fs::read(dir.join(KEY_FILE)).unwrap();
+        reopened.set_master_passphrase(None).unwrap();
+        assert_eq!(fs::read(dir.join(KEY_FILE)).unwrap(), key);
+        assert_eq!(reopened.get("claude/work").unwrap(), "sk-test-123");
+        // 写入通过临时文件完成，不应有残留
+        assert!(!dir.join(format!("{}.tmp", SECRETS_FILE)).exists());
+        assert!(!dir.join(format!("{}.tmp", KEY_FILE)).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,626 @@
+use crate::commands::filesystem::{format_system_time, is_sensitive_path, sort_files, FileInfo};
+use crate::ssh::{self, HostKeyPrompts, SshClientHandler, SshConnectOptions};
+use russh::client::Handle;
+use russh::Disconnect;
+use russh_sftp::client::fs::Metadata;
+use russh_sftp::client::SftpSession;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// 与本地文件命令一致，读写文本内容最大 10MB
+const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+/// 上传下载时每次读写的字节数
+const TRANSFER_CHUNK_SIZE: usize = 64 * 1024;
+/// 进度事件的最小间隔，传输完成时总会推送一次
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 传输方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferDirection {
+    Upload,
+    Download,
+}
+
+/// 上传下载进度事件（`sftp-progress-{connection_id}`）的负载
+#[derive(Debug, Clone, Serialize)]
+pub struct SftpProgress {
+    pub transfer_id: u64,
+    pub direction: TransferDirection,
+    pub local_path: String,
+    pub remote_path: String,
+    pub transferred: u64,
+    /// 文件总大小，服务端未返回大小时为空
+    pub total: Option<u64>,
+    pub done: bool,
+}
+
+/// 已建立的 SFTP 连接，按前端分配的连接编号索引
+pub struct SftpManager {
+    connections: Mutex<HashMap<u64, Arc<SftpConnection>>>,
+    prompts: HostKeyPrompts,
+    next_transfer_id: AtomicU64,
+}
+
+impl SftpManager {
+    pub fn new(prompts: HostKeyPrompts) -> Self {
+        Self {
+            connections: Mutex::new(HashMap::new()),
+            prompts,
+            next_transfer_id: AtomicU64::new(1),
+        }
+    }
+
+    /// 建立连接并返回远程主目录，已有同编号的连接时先断开旧连接
+    pub async fn connect(
+        &self,
+        connection_id: u64,
+        options: SshConnectOptions,
+        app_handle: AppHandle,
+    ) -> Result<String, String> {
+        let (handle, sftp) =
+            ssh::open_sftp(connection_id, &options, self.prompts.clone(), app_handle)
+                .await
+                .map_err(|e| e.to_string())?;
+        let home = sftp
+            .canonicalize(".")
+            .await
+            .map_err(|e| format!("无法获取远程主目录: {}", e))?;
+
+        tracing::info!(
+            "SFTP connection {} established to {}@{}:{}",
+            connection_id,
+            options.username,
+            options.host,
+            options.port
+        );
+        let connection = Arc::new(SftpConnection {
+            handle,
+            sftp,
+            home: home.clone(),
+        });
+        let previous = self
+            .connections
+            .lock()
+            .unwrap()
+            .insert(connection_id, connection);
+        if let Some(previous) = previous {
+            previous.close().await;
+        }
+        Ok(home)
+    }
+
+    pub async fn disconnect(&self, connection_id: u64) -> Result<(), String> {
+        let connection = self
+            .connections
+            .lock()
+            .unwrap()
+            .remove(&connection_id)
+            .ok_or_else(|| format!("SFTP 连接 {} 不存在", connection_id))?;
+        connection.close().await;
+        tracing::info!("SFTP connection {} closed", connection_id);
+        Ok(())
+    }
+
+    pub fn get(&self, connection_id: u64) -> Result<Arc<SftpConnection>, String> {
+        self.connections
+            .lock()
+            .unwrap()
+            .get(&connection_id)
+            .cloned()
+            .ok_or_else(|| format!("SFTP 连接 {} 不存在", connection_id))
+    }
+
+    /// 上传本地文件，进度通过 `sftp-progress-{connection_id}` 事件推送
+    pub async fn upload(
+        &self,
+        connection_id: u64,
+        local_path: &str,
+        remote_path: &str,
+        app_handle: AppHandle,
+    ) -> Result<SftpProgress, String> {
+        let connection = self.get(connection_id)?;
+        let remote_path = connection.resolve(remote_path);
+        if is_sensitive_path(local_path) {
+            return Err("拒绝访问：无法上传敏感系统文件".to_string());
+        }
+
+        let local = tokio::fs::File::open(local_path)
+            .await
+            .map_err(|e| format!("无法打开本地文件 {}: {}", local_path, e))?;
+        let total = local.metadata().await.ok().map(|metadata| metadata.len());
+        let remote = connection
+            .sftp
+            .create(remote_path.as_str())
+            .await
+            .map_err(|e| format!("无法创建远程文件 {}: {}", remote_path, e))?;
+
+        let progress = SftpProgress {
+            transfer_id: self.next_transfer_id.fetch_add(1, Ordering::Relaxed),
+            direction: TransferDirection::Upload,
+            local_path: local_path.to_string(),
+            remote_path,
+            transferred: 0,
+            total,
+            done: false,
+        };
+        transfer(local, remote, progress, connection_id, &app_handle).await
+    }
+
+    /// 下载远程文件到本地，进度通过 `sftp-progress-{connection_id}` 事件推送
+    pub async fn download(
+        &self,
+        connection_id: u64,
+        remote_path: &str,
+        local_path: &str,
+        app_handle: AppHandle,
+    ) -> Result<SftpProgress, String> {
+        let connection = self.get(connection_id)?;
+        let remote_path = connection.resolve(remote_path);
+        if is_sensitive_path(local_path) {
+            return Err("拒绝访问：无法写入敏感系统文件".to_string());
+        }
+
+        let remote = connection
+            .sftp
+            .open(remote_path.as_str())
+            .await
+            .map_err(|e| format!("无法打开远程文件 {}: {}", remote_path, e))?;
+        let total = remote
+            .metadata()
+            .await
+            .ok()
+            .and_then(|metadata| metadata.size);
+        let local = tokio::fs::File::create(local_path)
+            .await
+            .map_err(|e| format!("无法创建本地文件 {}: {}", local_path, e))?;
+
+        let progress = SftpProgress {
+            transfer_id: self.next_transfer_id.fetch_add(1, Ordering::Relaxed),
+            direction: TransferDirection::Download,
+            local_path: local_path.to_string(),
+            remote_path,
+            transferred: 0,
+            total,
+            done: false,
+        };
+        transfer(remote, local, progress, connection_id, &app_handle).await
+    }
+}
+
+/// 单个 SFTP 连接上的文件操作，与 `commands::filesystem` 中的本地命令一一对应
+///
+/// 相对路径相对于远程主目录，`~` 展开为远程主目录
+pub struct SftpConnection {
+    handle: Handle<SshClientHandler>,
+    sftp: SftpSession,
+    home: String,
+}
+
+impl SftpConnection {
+    pub async fn read_file(&self, path: &str) -> Result<String, String> {
+        let path = self.resolve(path);
+        let metadata = self.metadata(&path).await?;
+        if metadata.is_dir() {
+            return Err(format!("路径是目录，不是文件: {}", path));
+        }
+        if metadata.len() > MAX_FILE_SIZE {
+            return Err(format!(
+                "文件过大 ({:.2}MB)，超过最大限制 10MB",
+                metadata.len() as f64 / (1024.0 * 1024.0)
+            ));
+        }
+
+        let data = self
+            .sftp
+            .read(path.as_str())
+            .await
+            .map_err(|e| format!("读取文件失败: {}", e))?;
+        String::from_utf8(data).map_err(|_| format!("文件不是 UTF-8 文本: {}", path))
+    }
+
+    pub async fn write_file(&self, path: &str, content: &str) -> Result<(), String> {
+        if content.len() as u64 > MAX_FILE_SIZE {
+            return Err(format!(
+                "内容过大 ({:.2}MB)，超过最大限制 10MB",
+                content.len() as f64 / (1024.0 * 1024.0)
+            ));
+        }
+        let path = self.resolve(path);
+        let mut file = self
+            .sftp
+            .create(path.as_str())
+            .await
+            .map_err(|e| format!("写入文件失败: {}", e))?;
+        file.write_all(content.as_bytes())
+            .await
+            .map_err(|e| format!("写入文件失败: {}", e))?;
+        file.shutdown()
+            .await
+            .map_err(|e| format!("写入文件失败: {}", e))?;
+
+        tracing::info!("成功写入远程文件: {}", path);
+        Ok(())
+    }
+
+    pub async fn list_files(&self, dir: &str, show_hidden: bool) -> Result<Vec<FileInfo>, String> {
+        let dir = self.resolve(dir);
+        let entries = self
+            .sftp
+            .read_dir(dir.as_str())
+            .await
+            .map_err(|e| format!("读取目录失败: {}", e))?;
+
+        let mut files = Vec::new();
+        for entry in entries {
+            let name = entry.file_name();
+            if name == "." || name == ".." || (!show_hidden && name.starts_with('.')) {
+                continue;
+            }
+            let path = join_remote(&dir, &name);
+            // 目录列表中是链接本身的属性，跟随链接以便区分指向目录的链接
+            let metadata = if entry.file_type().is_symlink() {
+                self.sftp
+                    .metadata(path.as_str())
+                    .await
+                    .unwrap_or_else(|_| entry.metadata())
+            } else {
+                entry.metadata()
+            };
+            files.push(file_info(name, path, &metadata));
+        }
+
+        sort_files(&mut files);
+        Ok(files)
+    }
+
+    pub async fn create_directory(&self, path: &str, recursive: bool) -> Result<(), String> {
+        let path = self.resolve(path);
+        if !recursive {
+            return self
+                .sftp
+                .create_dir(path.as_str())
+                .await
+                .map_err(|e| format!("创建目录失败: {}", e));
+        }
+
+        for dir in ancestors(&path) {
+            if !self.exists(&dir).await? {
+                self.sftp
+                    .create_dir(dir.as_str())
+                    .await
+                    .map_err(|e| format!("创建目录 {} 失败: {}", dir, e))?;
+            }
+        }
+        tracing::info!("成功创建远程目录: {}", path);
+        Ok(())
+    }
+
+    pub async fn delete_file(&self, path: &str) -> Result<(), String> {
+        let path = self.resolve(path);
+        if self.metadata(&path).await?.is_dir() {
+            return Err(format!("路径是目录，请使用删除目录命令: {}", path));
+        }
+        self.sftp
+            .remove_file(path.as_str())
+            .await
+            .map_err(|e| format!("删除文件失败: {}", e))?;
+
+        tracing::info!("成功删除远程文件: {}", path);
+        Ok(())
+    }
+
+    pub async fn delete_directory(&self, path: &str, recursive: bool) -> Result<(), String> {
+        let path = self.resolve(path);
+        if !self.metadata(&path).await?.is_dir() {
+            return Err(format!("路径不是目录: {}", path));
+        }
+        if !recursive {
+            return self
+                .sftp
+                .remove_dir(path.as_str())
+                .await
+                .map_err(|e| format!("删除目录失败（目录可能不为空，请使用递归删除）: {}", e));
+        }
+
+        // 深度优先遍历，目录在其内容删除后再删除；指向目录的链接按文件删除
+        let mut stack = vec![(path.clone(), false)];
+        while let Some((dir, emptied)) = stack.pop() {
+            if emptied {
+                self.sftp
+                    .remove_dir(dir.as_str())
+                    .await
+                    .map_err(|e| format!("删除目录 {} 失败: {}", dir, e))?;
+                continue;
+            }
+            let entries = self
+                .sftp
+                .read_dir(dir.as_str())
+                .await
+                .map_err(|e| format!("读取目录 {} 失败: {}", dir, e))?;
+            stack.push((dir.clone(), true));
+            for entry in entries {
+                let name = entry.file_name();
+                if name == "." || name == ".." {
+                    continue;
+                }
+                let child = join_remote(&dir, &name);
+                if entry.file_type().is_dir() {
+                    stack.push((child, false));
+                } else {
+                    self.sftp
+                        .remove_file(child.as_str())
+                        .await
+                        .map_err(|e| format!("删除文件 {} 失败: {}", child, e))?;
+                }
+            }
+        }
+
+        tracing::info!("成功删除远程目录: {}", path);
+        Ok(())
+    }
+
+    /// SFTP 协议没有复制操作，内容经本机中转
+    pub async fn copy_file(
+        &self,
+        source: &str,
+        destination: &str,
+        overwrite: bool,
+    ) -> Result<(), String> {
+        let source = self.resolve(source);
+        let destination = self.resolve(destination);
+        if self.metadata(&source).await?.is_dir() {
+            return Err(format!("源路径是目录，不是文件: {}", source));
+        }
+        if !overwrite && self.exists(&destination).await? {
+            return Err(format!("目标文件已存在: {}", destination));
+        }
+
+        let mut reader = self
+            .sftp
+            .open(source.as_str())
+            .await
+            .map_err(|e| format!("复制文件失败: {}", e))?;
+        let mut writer = self
+            .sftp
+            .create(destination.as_str())
+            .await
+            .map_err(|e| format!("复制文件失败: {}", e))?;
+        tokio::io::copy(&mut reader, &mut writer)
+            .await
+            .map_err(|e| format!("复制文件失败: {}", e))?;
+        writer
+            .shutdown()
+            .await
+            .map_err(|e| format!("复制文件失败: {}", e))?;
+
+        tracing::info!("成功复制远程文件: {} -> {}", source, destination);
+        Ok(())
+    }
+
+    pub async fn move_file(
+        &self,
+        source: &str,
+        destination: &str,
+        overwrite: bool,
+    ) -> Result<(), String> {
+        let source = self.resolve(source);
+        let destination = self.resolve(destination);
+        if !self.exists(&source).await? {
+            return Err(format!("源路径不存在: {}", source));
+        }
+        if self.exists(&destination).await? {
+            if !overwrite {
+                return Err(format!("目标路径已存在: {}", destination));
+            }
+            if self.metadata(&destination).await?.is_dir() {
+                return Err(format!("目标路径是目录，无法覆盖: {}", destination));
+            }
+            // SFTP v3 的 rename 不会覆盖已有文件
+            self.sftp
+                .remove_file(destination.as_str())
+                .await
+                .map_err(|e| format!("移动文件失败: {}", e))?;
+        }
+        self.sftp
+            .rename(source.as_str(), destination.as_str())
+            .await
+            .map_err(|e| format!("移动文件失败: {}", e))?;
+
+        tracing::info!("成功移动远程文件: {} -> {}", source, destination);
+        Ok(())
+    }
+
+    pub async fn path_exists(&self, path: &str) -> Result<bool, String> {
+        self.exists(&self.resolve(path)).await
+    }
+
+    pub async fn get_file_metadata(&self, path: &str) -> Result<FileInfo, String> {
+        let path = self.resolve(path);
+        let metadata = self.metadata(&path).await?;
+        let name = path
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .filter(|name| !name.is_empty())
+            .unwrap_or("/")
+            .to_string();
+        Ok(file_info(name, path, &metadata))
+    }
+
+    // 相对路径和 ~ 都相对于远程主目录
+    fn resolve(&self, path: &str) -> String {
+        if path.is_empty() || path == "~" {
+            self.home.clone()
+        } else if let Some(rest) = path.strip_prefix("~/") {
+            join_remote(&self.home, rest)
+        } else if path.starts_with('/') {
+            path.to_string()
+        } else {
+            join_remote(&self.home, path)
+        }
+    }
+
+    async fn metadata(&self, path: &str) -> Result<Metadata, String> {
+        self.sftp
+            .metadata(path)
+            .await
+            .map_err(|e| format!("路径不存在或无法访问: {} ({})", path, e))
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool, String> {
+        self.sftp
+            .try_exists(path)
+            .await
+            .map_err(|e| format!("无法访问远程路径 {}: {}", path, e))
+    }
+
+    async fn close(&self) {
+        let _ = self.sftp.close().await;
+        let _ = self
+            .handle
+            .disconnect(Disconnect::ByApplication, "", "en")
+            .await;
+    }
+}
+
+// 按块复制并推送进度事件
+async fn transfer<R, W>(
+    mut reader: R,
+    mut writer: W,
+    mut progress: SftpProgress,
+    connection_id: u64,
+    app_handle: &AppHandle,
+) -> Result<SftpProgress, String>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let event = format!("sftp-progress-{}", connection_id);
+    let mut buf = vec![0u8; TRANSFER_CHUNK_SIZE];
+    let mut last_emit = Instant::now();
+
+    loop {
+        let n = reader
+            .read(&mut buf)
+            .await
+            .map_err(|e| format!("传输 {} 失败: {}", progress.remote_path, e))?;
+        if n == 0 {
+            break;
+        }
+        writer
+            .write_all(&buf[..n])
+            .await
+            .map_err(|e| format!("传输 {} 失败: {}", progress.remote_path, e))?;
+        progress.transferred += n as u64;
+
+        if last_emit.elapsed() >= PROGRESS_INTERVAL {
+            let _ = app_handle.emit(&event, progress.clone());
+            last_emit = Instant::now();
+        }
+    }
+    writer
+        .shutdown()
+        .await
+        .map_err(|e| format!("传输 {} 失败: {}", progress.remote_path, e))?;
+
+    progress.done = true;
+    let _ = app_handle.emit(&event, progress.clone());
+    tracing::info!(
+        "SFTP 传输完成: {} <-> {} ({} 字节)",
+        progress.local_path,
+        progress.remote_path,
+        progress.transferred
+    );
+    Ok(progress)
+}
+
+// 远程路径总是使用 `/` 分隔，不受本机平台影响
+fn join_remote(dir: &str, name: &str) -> String {
+    if dir.ends_with('/') {
+        format!("{}{}", dir, name)
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+// 从最上层开始列出路径的每一级目录（不含根目录）
+fn ancestors(path: &str) -> Vec<String> {
+    let mut current = if path.starts_with('/') {
+        String::from("/")
+    } else {
+        String::new()
+    };
+    path.split('/')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            current = if current.is_empty() {
+                part.to_string()
+            } else {
+                join_remote(&current, part)
+            };
+            current.clone()
+        })
+        .collect()
+}
+
+// SFTP v3 不提供创建时间
+fn file_info(name: String, path: String, metadata: &Metadata) -> FileInfo {
+    FileInfo {
+        name,
+        path,
+        is_dir: metadata.is_dir(),
+        size: metadata.len(),
+        modified: metadata
+            .mtime
+            .map(|secs| format_system_time(UNIX_EPOCH + Duration::from_secs(secs as u64))),
+        created: None,
+        permissions: metadata
+            .permissions
+            .map(|mode| format!("{:o}", mode & 0o777)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_paths() {
+        assert_eq!(join_remote("/home/dev", "src"), "/home/dev/src");
+        assert_eq!(join_remote("/", "etc"), "/etc");
+        assert_eq!(
+            ancestors("/srv//app/logs/"),
+            vec!["/srv", "/srv/app", "/srv/app/logs"]
+        );
+        assert_eq!(ancestors("build/out"), vec!["build", "build/out"]);
+    }
+
+    #[test]
+    fn test_file_info_from_metadata() {
+        let metadata = Metadata {
+            size: Some(4096),
+            permissions: Some(0o040755),
+            mtime: Some(0),
+            ..Default::default()
+        };
+        let info = file_info("src".to_string(), "/home/dev/src".to_string(), &metadata);
+        assert!(info.is_dir);
+        assert_eq!(info.size, 4096);
+        assert_eq!(info.permissions.as_deref(), Some("755"));
+        assert!(info.modified.is_some());
+        assert!(info.created.is_none());
+
+        let info = file_info("a".to_string(), "/a".to_string(), &Metadata::default());
+        assert!(!info.is_dir);
+        assert_eq!(
+            (info.size, info.modified, info.permissions),
+            (0, None, None)
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// 每个会话最多保留的命令记录数
+const MAX_COMMAND_RECORDS: usize = 200;
+
+/// OSC 133 语义标记
+#[derive(Debug, Clone, PartialEq)]
+pub enum SemanticMark {
+    /// `A`：提示符开始
+    PromptStart,
+    /// `B`：提示符结束，用户开始输入
+    PromptEnd,
+    /// `C`：命令开始执行，可携带 `cmdline=` 命令文本
+    CommandStart { command: Option<String> },
+    /// `D`：命令执行结束，可携带退出码
+    CommandEnd { exit_code: Option<i32> },
+}
+
+impl SemanticMark {
+    /// 解析 OSC 133 的内容（不含 `133;` 前缀）
+    pub fn parse(payload: &str) -> Option<Self> {
+        let mut parts = payload.split(';');
+        match parts.next()? {
+            "A" => Some(Self::PromptStart),
+            "B" => Some(Self::PromptEnd),
+            "C" => Some(Self::CommandStart {
+                command: parts
+                    .find_map(|part| part.strip_prefix("cmdline="))
+                    .map(str::to_string),
+            }),
+            "D" => Some(Self::CommandEnd {
+                exit_code: parts.next().and_then(|code| code.trim().parse().ok()),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// 查找文本中所有完整的 OSC 序列，返回 (起始位置, 结束位置, 内容)
+///
+/// 终止符可以是 BEL 或 ST（`ESC \`），结束位置指向终止符之后
+pub fn osc_sequences(data: &str) -> Vec<(usize, usize, &str)> {
+    let mut sequences = Vec::new();
+    let mut pos = 0;
+    while let Some(found) = data[pos..].find("\x1b]") {
+        let start = pos + found;
+        let body = start + 2;
+        let rest = &data[body..];
+        let terminator = rest.find(['\x07', '\x1b']);
+        match terminator {
+            Some(end) if rest[end..].starts_with('\x07') => {
+                sequences.push((start, body + end + 1, &rest[..end]));
+                pos = body + end + 1;
+            }
+            Some(end) if rest[end..].starts_with("\x1b\\") => {
+                sequences.push((start, body + end + 2, &rest[..end]));
+                pos = body + end + 2;
+            }
+            // 被其他转义序列打断的 OSC 直接忽略
+            Some(end) => pos = body + end,
+            None => break,
+        }
+    }
+    sequences
+}
+
+/// 一条交互式命令的执行记录
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandRecord {
+    pub session_id: u64,
+    /// 会话内递增的编号
+    pub id: u64,
+    pub command: String,
+    pub cwd: String,
+    /// 命令输出在 scrollback 中的起始偏移量
+    pub output_start: u64,
+    /// 命令输出的结束偏移量，命令仍在运行时为空
+    pub output_end: Option<u64>,
+    pub started_at: String,
+    pub duration_ms: Option<u64>,
+    /// shell 未报告退出码（例如命令被中断后直接显示提示符）时为空
+    pub exit_code: Option<i32>,
+}
+
+/// 根据 OSC 133 标记记录会话中每条命令的边界
+pub struct CommandTracker {
+    session_id: u64,
+    next_id: u64,
+    records: VecDeque<CommandRecord>,
+    running: Option<(CommandRecord, Instant)>,
+}
+
+impl CommandTracker {
+    pub fn new(session_id: u64) -> Self {
+        Self {
+            session_id,
+            next_id: 1,
+            records: VecDeque::new(),
+            running: None,
+        }
+    }
+
+    /// 处理一个标记，`offset` 为标记在输出流中的偏移量
+    ///
+    /// 有命令结束时返回它的记录
+    pub fn mark(&mut self, mark: SemanticMark, offset: u64, cwd: &str) -> Option<CommandRecord> {
+        match mark {
+            SemanticMark::CommandStart { command } => {
+                // 上一条命令没有收到结束标记
+                let unfinished = self.finish(offset, None);
+                let record = CommandRecord {
+                    session_id: self.session_id,
+                    id: self.next_id,
+                    command: command.unwrap_or_default().trim().to_string(),
+                    cwd: cwd.to_string(),
+                    output_start: offset,
+                    output_end: None,
+                    started_at: chrono::Local::now().to_rfc3339(),
+                    duration_ms: None,
+                    exit_code: None,
+                };
+                self.next_id += 1;
+                self.running = Some((record, Instant::now()));
+                unfinished
+            }
+            SemanticMark::CommandEnd { exit_code } => self.finish(offset, exit_code),
+            // 没有 D 标记的 shell 在显示下一个提示符时结束命令
+            SemanticMark::PromptStart => self.finish(offset, None),
+            SemanticMark::PromptEnd => None,
+        }
+    }
+
+    fn finish(&mut self, offset: u64, exit_code: Option<i32>) -> Option<CommandRecord> {
+        let (mut record, started) = self.running.take()?;
+        record.output_end = Some(offset);
+        record.duration_ms = Some(started.elapsed().as_millis() as u64);
+        record.exit_code = exit_code;
+
+        self.records.push_back(record.clone());
+        if self.records.len() > MAX_COMMAND_RECORDS {
+            self.records.pop_front();
+        }
+        Some(record)
+    }
+
+    /// 已结束的命令，以及正在运行的命令（如果有）
+    pub fn list(&self) -> Vec<CommandRecord> {
+        self.records
+            .iter()
+            .chain(self.running.as_ref().map(|(record, _)| record))
+            .cloned()
+            .collect()
+    }
+
+    pub fn get(&self, id: u64) -> Option<CommandRecord> {
+        self.list().into_iter().find(|record| record.id == id)
+    }
+
+    /// 最近一条以非零退出码结束的命令
+    pub fn last_failed(&self) -> Option<CommandRecord> {
+        self.records
+            .iter()
+            .rev()
+            .find(|record| matches!(record.exit_code, Some(code) if code != 0))
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_marks() {
+        assert_eq!(SemanticMark::parse("A"), Some(SemanticMark::PromptStart));
+        assert_eq!(
+            SemanticMark::parse("A;k=s"),
+            Some(SemanticMark::PromptStart)
+        );
+        assert_eq!(
+            SemanticMark::parse("C;cmdline=ls -la"),
+            Some(SemanticMark::CommandStart {
+                command: Some("ls -la".to_string())
+            })
+        );
+        assert_eq!(
+            SemanticMark::parse("C"),
+            Some(SemanticMark::CommandStart { command: None })
+        );
+        assert_eq!(
+            SemanticMark::parse("D;127"),
+            Some(SemanticMark::CommandEnd {
+                exit_code: Some(127)
+            })
+        );
+        assert_eq!(
+            SemanticMark::parse("D"),
+            Some(SemanticMark::CommandEnd { exit_code: None })
+        );
+        assert_eq!(SemanticMark::parse("Z"), None);
+    }
+
+    #[test]
+    fn test_osc_sequences() {
+        let data = "a\x1b]133;A\x07b\x1b]7;file://h/tmp\x1b\\c\x1b]0;title\x1b[0md\x1b]133;D";
+        let found: Vec<_> = osc_sequences(data)
+            .into_iter()
+            .map(|(start, end, payload)| (&data[start..end], payload))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("\x1b]133;A\x07", "133;A"),
+                ("\x1b]7;file://h/tmp\x1b\\", "7;file://h/tmp"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_tracker() {
+        let mut tracker = CommandTracker::new(7);
+        assert!(tracker.mark(SemanticMark::PromptStart, 0, "/").is_none());
+        assert!(tracker
+            .mark(
+                SemanticMark::CommandStart {
+                    command: Some("false".to_string())
+                },
+                10,
+                "/tmp"
+            )
+            .is_none());
+        assert_eq!(tracker.list().len(), 1);
+
+        let finished = tracker
+            .mark(SemanticMark::CommandEnd { exit_code: Some(1) }, 25, "/tmp")
+            .unwrap();
+        assert_eq!(finished.session_id, 7);
+        assert_eq!(finished.command, "false");
+        assert_eq!(finished.cwd, "/tmp");
+        assert_eq!((finished.output_start, finished.output_end), (10, Some(25)));
+        assert_eq!(finished.exit_code, Some(1));
+
+        // 没有 D 标记时由下一个提示符结束命令
+        tracker.mark(
+            SemanticMark::CommandStart {
+                command: Some("true".to_string()),
+            },
+            40,
+            "/tmp",
+        );
+        let finished = tracker.mark(SemanticMark::PromptStart, 50, "/tmp").unwrap();
+        assert_eq!(finished.exit_code, None);
+
+        // 空行回车只有 D/A，没有正在运行的命令
+        assert!(tracker
+            .mark(SemanticMark::CommandEnd { exit_code: Some(0) }, 60, "/tmp")
+            .is_none());
+
+        assert_eq!(tracker.last_failed().unwrap().id, 1);
+        assert_eq!(tracker.get(2).unwrap().command, "true");
+    }
+}
@@ -0,0 +1,1487 @@
+use crate::ssh_forward::{ForwardSpec, RemoteForwards, SshForwards};
+use crate::ssh_profiles::SshProfile;
+use crate::terminal::{OutputSink, TerminalExit, OUTPUT_BUFFER_MS};
+use anyhow::{anyhow, Result};
+use russh::client::{self, Handle, Msg};
+use russh::keys::agent::client::AgentClient;
+use russh::keys::known_hosts::learn_known_hosts_path;
+use russh::keys::{
+    check_known_hosts_path, load_secret_key, HashAlg, PrivateKeyWithHashAlg, PublicKey,
+};
+use russh::{Channel, ChannelMsg, Disconnect};
+use russh_sftp::client::SftpSession;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+const SSH_CONNECT_TIMEOUT_SECS: u64 = 15;
+const HOST_KEY_PROMPT_TIMEOUT_SECS: u64 = 120;
+
+/// SSH 认证方式
+#[derive(Debug, Clone)]
+pub enum SshAuth {
+    /// 密码认证
+    Password(String),
+    /// 私钥认证（可选私钥口令）
+    PublicKey {
+        path: PathBuf,
+        passphrase: Option<String>,
+    },
+    /// 通过 ssh-agent（SSH_AUTH_SOCK）认证
+    Agent,
+}
+
+/// SSH 连接参数
+#[derive(Debug, Clone)]
+pub struct SshConnectOptions {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    /// 按顺序尝试的认证方式，第一个成功的生效
+    pub auth: Vec<SshAuth>,
+    pub cols: u16,
+    pub rows: u16,
+    /// 打开 shell 前设置的远程环境变量（服务端可能通过 AcceptEnv 拒绝）
+    pub env: HashMap<String, String>,
+    /// shell 启动后自动执行的命令
+    pub startup_command: Option<String>,
+    /// 按顺序经过的跳板机（等同 ProxyJump），最后一跳连接目标主机
+    pub jump_hosts: Vec<SshHop>,
+    /// 连接建立后启动的端口转发（-L / -R / -D）
+    pub forwards: Vec<ForwardSpec>,
+    pub keepalive: SshKeepalive,
+    pub reconnect: SshReconnect,
+    pub host_key_checking: HostKeyChecking,
+}
+
+/// 未知主机密钥的处理方式，对应 OpenSSH 的 StrictHostKeyChecking
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HostKeyChecking {
+    /// 未知或变更的密钥都询问用户
+    #[default]
+    Ask,
+    /// 自动信任并记录未知密钥，变更的密钥直接拒绝
+    AcceptNew,
+}
+
+/// 保活设置，等同 OpenSSH 的 ServerAliveInterval / ServerAliveCountMax
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SshKeepalive {
+    /// 连接空闲多少秒后发送保活请求，0 表示关闭
+    pub interval_secs: u64,
+    /// 连续多少次保活请求无响应后判定连接已断开
+    pub max_missed: usize,
+}
+
+impl Default for SshKeepalive {
+    fn default() -> Self {
+        Self {
+            interval_secs: 15,
+            max_missed: 3,
+        }
+    }
+}
+
+impl SshKeepalive {
+    fn client_config(&self) -> client::Config {
+        client::Config {
+            keepalive_interval: (self.interval_secs > 0)
+                .then(|| Duration::from_secs(self.interval_secs)),
+            keepalive_max: self.max_missed,
+            ..Default::default()
+        }
+    }
+}
+
+/// 连接意外断开后的自动重连设置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SshReconnect {
+    pub enabled: bool,
+    /// 最多重连次数，0 表示不限次数
+    pub max_attempts: u32,
+    /// 第一次重连前的等待时间，之后每次翻倍
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// 重连后再次执行 startup_command
+    pub rerun_startup_command: bool,
+    /// 连接后挂载的远程 tmux 会话（不存在时创建），重连后自动重新挂载
+    pub tmux_session: Option<String>,
+}
+
+impl Default for SshReconnect {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_attempts: 8,
+            initial_delay_ms: 1000,
+            max_delay_ms: 30_000,
+            rerun_startup_command: false,
+            tmux_session: None,
+        }
+    }
+}
+
+impl SshReconnect {
+    /// 第 attempt 次（从 1 开始）重连前的等待时间
+    fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(16);
+        Duration::from_millis(
+            self.initial_delay_ms
+                .saturating_mul(factor)
+                .min(self.max_delay_ms.max(self.initial_delay_ms)),
+        )
+    }
+}
+
+/// SSH 连接状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SshState {
+    Connecting,
+    Connected,
+    Reconnecting,
+    Failed,
+}
+
+/// 连接状态事件（`ssh-state-{id}`）的负载
+#[derive(Debug, Clone, Serialize)]
+pub struct SshStateEvent {
+    pub session_id: u64,
+    pub state: SshState,
+    /// 重连时为第几次尝试
+    pub attempt: Option<u32>,
+    /// 重连时，本次尝试前等待的毫秒数
+    pub delay_ms: Option<u64>,
+    /// 断开或失败的原因
+    pub error: Option<String>,
+}
+
+/// 连接链中的一跳：跳板机或目标主机
+#[derive(Debug, Clone)]
+pub struct SshHop {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub auth: Vec<SshAuth>,
+}
+
+impl SshHop {
+    /// 解析 ProxyJump 格式的 `[user@]host[:port]`，未指定用户时使用 default_user，
+    /// 认证方式为 agent 和默认私钥
+    pub fn parse(spec: &str, default_user: &str) -> Result<Self> {
+        let spec = spec.trim();
+        let (username, address) = match spec.rsplit_once('@') {
+            Some((user, address)) => (user.to_string(), address),
+            None => (default_user.to_string(), spec),
+        };
+        // IPv6 地址用方括号包围，例如 `[::1]:2222`
+        let (host, port) = if let Some(rest) = address.strip_prefix('[') {
+            let (host, rest) = rest
+                .split_once(']')
+                .ok_or_else(|| anyhow!("无法解析跳板机 '{}'", spec))?;
+            (host, rest.strip_prefix(':'))
+        } else {
+            match address.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (address, None),
+            }
+        };
+        let port = match port {
+            Some(port) => port
+                .parse()
+                .map_err(|_| anyhow!("跳板机 '{}' 的端口无效: {}", spec, port))?,
+            None => 22,
+        };
+        if host.is_empty() || username.is_empty() {
+            return Err(anyhow!("无法解析跳板机 '{}'", spec));
+        }
+
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            username,
+            auth: auth_methods(None, None, None),
+        })
+    }
+
+    // 用于错误信息和日志
+    fn label(&self) -> String {
+        format!("{}@{}:{}", self.username, self.host, self.port)
+    }
+}
+
+impl SshConnectOptions {
+    /// 根据前端传入的参数构造认证列表，规则见 `auth_methods`
+    pub fn new(
+        host: String,
+        port: u16,
+        username: String,
+        password: Option<String>,
+        identity_file: Option<String>,
+        passphrase: Option<String>,
+    ) -> Self {
+        Self {
+            host,
+            port,
+            username,
+            auth: auth_methods(password, identity_file, passphrase),
+            cols: 80,
+            rows: 24,
+            env: HashMap::new(),
+            startup_command: None,
+            jump_hosts: Vec::new(),
+            forwards: Vec::new(),
+            keepalive: SshKeepalive::default(),
+            reconnect: SshReconnect::default(),
+            host_key_checking: HostKeyChecking::default(),
+        }
+    }
+
+    /// 从已保存的 SSH 配置构造连接参数，未设置用户时使用本地 $USER
+    ///
+    /// 跳板机名称与 `profiles` 中的配置同名时（导入的 ProxyJump 通常是 Host 别名），
+    /// 使用该配置的主机、端口、用户、私钥和它自己的跳板机；
+    /// `jump_password` 用于取出该配置保存的密码
+    pub fn from_profile(
+        profile: &SshProfile,
+        password: Option<String>,
+        passphrase: Option<String>,
+        profiles: &[SshProfile],
+        jump_password: &dyn Fn(&SshProfile) -> Result<Option<String>, String>,
+    ) -> Result<Self> {
+        let username = profile
+            .user
+            .clone()
+            .or_else(|| std::env::var("USER").ok())
+            .ok_or_else(|| anyhow!("SSH 配置 '{}' 未指定用户名", profile.name))?;
+
+        let mut options = Self::new(
+            profile.host.clone(),
+            profile.port,
+            username,
+            password,
+            profile.identity_file.clone(),
+            passphrase,
+        );
+        options.env = profile.env.clone();
+        options.startup_command = profile.startup_command.clone();
+        options.jump_hosts = profile_jump_hosts(
+            profile,
+            &options.username,
+            profiles,
+            jump_password,
+            &mut vec![profile.name.clone()],
+        )?;
+        options.forwards = profile.forwards.clone();
+        options.keepalive = profile.keepalive.clone();
+        options.reconnect = profile.reconnect.clone();
+        options.host_key_checking = profile.host_key_checking;
+        Ok(options)
+    }
+
+    /// 目标主机这一跳
+    pub fn target(&self) -> SshHop {
+        SshHop {
+            host: self.host.clone(),
+            port: self.port,
+            username: self.username.clone(),
+            auth: self.auth.clone(),
+        }
+    }
+}
+
+// 解析配置的跳板机，未指定用户时沿用 default_user，`none` 表示直连。
+// 引用已保存配置的跳板机展开为该配置的参数，它自己的跳板机排在它之前；
+// visited 记录当前链上的配置名，用于发现循环引用
+fn profile_jump_hosts(
+    profile: &SshProfile,
+    default_user: &str,
+    profiles: &[SshProfile],
+    jump_password: &dyn Fn(&SshProfile) -> Result<Option<String>, String>,
+    visited: &mut Vec<String>,
+) -> Result<Vec<SshHop>> {
+    let Some(jump_host) = profile.jump_host.as_deref().filter(|j| *j != "none") else {
+        return Ok(Vec::new());
+    };
+
+    let mut hops = Vec::new();
+    for spec in jump_host
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+    {
+        let hop = SshHop::parse(spec, default_user)?;
+        let Some(jump) = profiles.iter().find(|p| p.name == hop.host) else {
+            hops.push(hop);
+            continue;
+        };
+        if visited.contains(&jump.name) {
+            return Err(anyhow!("跳板机配置 '{}' 存在循环引用", jump.name));
+        }
+
+        // spec 中显式写出的用户和端口优先于配置
+        let (user, address) = match spec.rsplit_once('@') {
+            Some((user, address)) => (Some(user.to_string()), address),
+            None => (None, spec),
+        };
+        let username = user
+            .or_else(|| jump.user.clone())
+            .unwrap_or_else(|| default_user.to_string());
+        let port = if address == jump.name {
+            jump.port
+        } else {
+            hop.port
+        };
+        let password = jump_password(jump).map_err(|e| anyhow!(e))?;
+
+        visited.push(jump.name.clone());
+        hops.extend(profile_jump_hosts(
+            jump,
+            &username,
+            profiles,
+            jump_password,
+            visited,
+        )?);
+        visited.pop();
+        hops.push(SshHop {
+            host: jump.host.clone(),
+            port,
+            username,
+            auth: auth_methods(password, jump.identity_file.clone(), None),
+        });
+    }
+    Ok(hops)
+}
+
+/// 根据前端传入的参数构造认证列表
+///
+/// - 提供了密码：仅使用密码认证
+/// - 提供了私钥路径：使用该私钥
+/// - 否则：与 OpenSSH 一致，先尝试 agent，再尝试 ~/.ssh 下的默认私钥
+fn auth_methods(
+    password: Option<String>,
+    identity_file: Option<String>,
+    passphrase: Option<String>,
+) -> Vec<SshAuth> {
+    if let Some(password) = password {
+        vec![SshAuth::Password(password)]
+    } else if let Some(path) = identity_file {
+        vec![SshAuth::PublicKey {
+            path: PathBuf::from(expand_home(&path)),
+            passphrase,
+        }]
+    } else {
+        let mut auth = vec![SshAuth::Agent];
+        auth.extend(
+            default_identity_files()
+                .into_iter()
+                .map(|path| SshAuth::PublicKey {
+                    path,
+                    passphrase: passphrase.clone(),
+                }),
+        );
+        auth
+    }
+}
+
+/// 发起主机密钥提示的连接类型；终端会话和 SFTP 连接各自编号，编号可能相同
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HostKeyPromptKind {
+    #[default]
+    Terminal,
+    Sftp,
+}
+
+/// 主机密钥提示事件（`ssh-hostkey-prompt`）的负载
+#[derive(Debug, Clone, Serialize)]
+pub struct HostKeyPrompt {
+    pub kind: HostKeyPromptKind,
+    /// 终端会话编号或 SFTP 连接编号，由 kind 区分
+    pub session_id: u64,
+    pub host: String,
+    pub port: u16,
+    pub key_type: String,
+    /// SHA256 指纹，例如 `SHA256:...`
+    pub fingerprint: String,
+    /// true 表示 known_hosts 中已有该主机的不同密钥（可能遭到中间人攻击）
+    pub changed: bool,
+    /// 冲突记录在 known_hosts 中的行号
+    pub line: Option<usize>,
+}
+
+type PromptKey = (HostKeyPromptKind, u64);
+
+/// 等待用户确认的主机密钥提示，按 (kind, session_id) 索引
+#[derive(Clone, Default)]
+pub struct HostKeyPrompts {
+    pending: Arc<Mutex<HashMap<PromptKey, oneshot::Sender<bool>>>>,
+}
+
+impl HostKeyPrompts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn register(&self, kind: HostKeyPromptKind, session_id: u64) -> oneshot::Receiver<bool> {
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert((kind, session_id), tx);
+        rx
+    }
+
+    fn cancel(&self, kind: HostKeyPromptKind, session_id: u64) {
+        self.pending.lock().unwrap().remove(&(kind, session_id));
+    }
+
+    /// 接受或拒绝会话当前等待中的主机密钥
+    pub fn respond(&self, kind: HostKeyPromptKind, session_id: u64, accept: bool) -> Result<()> {
+        let tx = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(&(kind, session_id))
+            .ok_or_else(|| anyhow!("会话 {} 没有等待确认的主机密钥", session_id))?;
+        tx.send(accept)
+            .map_err(|_| anyhow!("会话 {} 的连接已中止", session_id))
+    }
+}
+
+/// 主机密钥校验策略
+#[derive(Clone)]
+pub enum HostKeyPolicy {
+    /// 未知或变更的密钥通过 `ssh-hostkey-prompt` 事件询问用户
+    Prompt {
+        app_handle: AppHandle,
+        prompts: HostKeyPrompts,
+        kind: HostKeyPromptKind,
+    },
+    /// 自动信任并记录未知密钥，拒绝变更的密钥（等同 StrictHostKeyChecking=accept-new）
+    AcceptNew,
+}
+
+impl HostKeyPolicy {
+    /// 按连接参数中的 `host_key_checking` 选择策略
+    pub fn new(
+        checking: HostKeyChecking,
+        app_handle: AppHandle,
+        prompts: HostKeyPrompts,
+        kind: HostKeyPromptKind,
+    ) -> Self {
+        match checking {
+            HostKeyChecking::Ask => Self::Prompt {
+                app_handle,
+                prompts,
+                kind,
+            },
+            HostKeyChecking::AcceptNew => Self::AcceptNew,
+        }
+    }
+}
+
+/// russh 客户端事件处理器，负责按 known_hosts 校验主机密钥，
+/// 并把服务端为远程转发打开的通道交给 RemoteForwards
+pub struct SshClientHandler {
+    session_id: u64,
+    host: String,
+    port: u16,
+    known_hosts: PathBuf,
+    policy: HostKeyPolicy,
+    remote_forwards: RemoteForwards,
+    // 经跳板机连接时持有上一跳的连接，本连接结束时随之断开
+    _via: Option<Handle<SshClientHandler>>,
+}
+
+impl SshClientHandler {
+    pub fn new(
+        session_id: u64,
+        host: &str,
+        port: u16,
+        policy: HostKeyPolicy,
+        remote_forwards: RemoteForwards,
+        via: Option<Handle<SshClientHandler>>,
+    ) -> Self {
+        Self {
+            session_id,
+            host: host.to_string(),
+            port,
+            known_hosts: default_known_hosts_path(),
+            policy,
+            remote_forwards,
+            _via: via,
+        }
+    }
+
+    async fn ask_user(&self, prompt: HostKeyPrompt) -> bool {
+        let HostKeyPolicy::Prompt {
+            app_handle,
+            prompts,
+            kind,
+        } = &self.policy
+        else {
+            // accept-new：只信任从未见过的主机
+            return !prompt.changed;
+        };
+
+        let rx = prompts.register(*kind, self.session_id);
+        if let Err(e) = app_handle.emit("ssh-hostkey-prompt", prompt) {
+            eprintln!("Failed to emit host key prompt: {}", e);
+            prompts.cancel(*kind, self.session_id);
+            return false;
+        }
+
+        match tokio::time::timeout(Duration::from_secs(HOST_KEY_PROMPT_TIMEOUT_SECS), rx).await {
+            Ok(Ok(accept)) => accept,
+            _ => {
+                prompts.cancel(*kind, self.session_id);
+                println!("Host key prompt for session {} timed out", self.session_id);
+                false
+            }
+        }
+    }
+}
+
+impl client::Handler for SshClientHandler {
+    type Error = russh::Error;
+
+    async fn check_server_key(
+        &mut self,
+        server_public_key: &PublicKey,
+    ) -> Result<bool, Self::Error> {
+        let (changed, line) = match check_known_hosts_path(
+            &self.host,
+            self.port,
+            server_public_key,
+            &self.known_hosts,
+        ) {
+            Ok(true) => return Ok(true),
+            Ok(false) => (false, None),
+            Err(russh::keys::Error::KeyChanged { line }) => (true, Some(line)),
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", self.known_hosts.display(), e);
+                (false, None)
+            }
+        };
+
+        let kind = match &self.policy {
+            HostKeyPolicy::Prompt { kind, .. } => *kind,
+            HostKeyPolicy::AcceptNew => HostKeyPromptKind::default(),
+        };
+        let prompt = HostKeyPrompt {
+            kind,
+            session_id: self.session_id,
+            host: self.host.clone(),
+            port: self.port,
+            key_type: server_public_key.algorithm().as_str().to_string(),
+            fingerprint: server_public_key.fingerprint(HashAlg::Sha256).to_string(),
+            changed,
+            line,
+        };
+
+        if !self.ask_user(prompt).await {
+            println!("Host key for {}:{} rejected", self.host, self.port);
+            return Ok(false);
+        }
+
+        // 记住新密钥；如果是变更的密钥，先删除旧记录
+        if let Some(line) = line {
+            if let Err(e) = remove_known_hosts_line(&self.known_hosts, line) {
+                eprintln!("Failed to remove stale known_hosts entry: {}", e);
+            }
+        }
+        if let Err(e) =
+            learn_known_hosts_path(&self.host, self.port, server_public_key, &self.known_hosts)
+        {
+            eprintln!("Failed to update {}: {}", self.known_hosts.display(), e);
+        }
+        Ok(true)
+    }
+
+    async fn server_channel_open_forwarded_tcpip(
+        &mut self,
+        channel: Channel<Msg>,
+        _connected_address: &str,
+        connected_port: u32,
+        originator_address: &str,
+        originator_port: u32,
+        _session: &mut client::Session,
+    ) -> Result<(), Self::Error> {
+        println!(
+            "Remote forward connection on port {} from {}:{}",
+            connected_port, originator_address, originator_port
+        );
+        self.remote_forwards.accept(channel, connected_port);
+        Ok(())
+    }
+}
+
+/// 发送给 SSH 会话任务的输入
+enum SshInput {
+    Data(Vec<u8>),
+    Resize { cols: u16, rows: u16 },
+    Close,
+}
+
+/// SSH 终端会话句柄，由 TerminalSession 持有
+pub struct SshChannelHandle {
+    tx: mpsc::UnboundedSender<SshInput>,
+    forwards: SshForwards,
+}
+
+impl SshChannelHandle {
+    /// 连接上的端口转发
+    pub fn forwards(&self) -> SshForwards {
+        self.forwards.clone()
+    }
+
+    pub fn writer(&self) -> SshWriter {
+        SshWriter {
+            tx: self.tx.clone(),
+        }
+    }
+
+    pub fn resize(&self, cols: u16, rows: u16) -> Result<()> {
+        self.tx
+            .send(SshInput::Resize { cols, rows })
+            .map_err(|_| anyhow!("SSH session already closed"))
+    }
+
+    pub fn close(&self) {
+        let _ = self.tx.send(SshInput::Close);
+    }
+}
+
+/// 将同步写入转发到 SSH 会话任务，使 write_terminal 对 PTY 和 SSH 通用
+pub struct SshWriter {
+    tx: mpsc::UnboundedSender<SshInput>,
+}
+
+impl Write for SshWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.tx
+            .send(SshInput::Data(buf.to_vec()))
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 建立 SSH 连接、校验主机密钥并完成认证
+///
+/// 配置了跳板机时依次连接每一跳，后一跳经前一跳的 direct-tcpip 通道建立，
+/// 错误信息中注明失败的是哪一跳
+pub async fn connect(
+    session_id: u64,
+    options: &SshConnectOptions,
+    policy: HostKeyPolicy,
+    remote_forwards: RemoteForwards,
+) -> Result<Handle<SshClientHandler>> {
+    let config = Arc::new(options.keepalive.client_config());
+    let hops = options.jump_hosts.len();
+    let mut via = None;
+    for (index, hop) in options.jump_hosts.iter().enumerate() {
+        let handle = connect_hop(
+            session_id,
+            hop,
+            via,
+            Arc::clone(&config),
+            policy.clone(),
+            RemoteForwards::default(),
+        )
+        .await
+        .map_err(|e| {
+            anyhow!(
+                "跳板机 {}（第 {}/{} 跳）连接失败: {}",
+                hop.label(),
+                index + 1,
+                hops,
+                e
+            )
+        })?;
+        via = Some(handle);
+    }
+
+    let target = options.target();
+    connect_hop(session_id, &target, via, config, policy, remote_forwards)
+        .await
+        .map_err(|e| match hops {
+            0 => e,
+            _ => anyhow!(
+                "经 {} 个跳板机连接目标主机 {} 失败: {}",
+                hops,
+                target.label(),
+                e
+            ),
+        })
+}
+
+// 连接单跳；via 为空时直接建立 TCP 连接
+async fn connect_hop(
+    session_id: u64,
+    hop: &SshHop,
+    via: Option<Handle<SshClientHandler>>,
+    config: Arc<client::Config>,
+    policy: HostKeyPolicy,
+    remote_forwards: RemoteForwards,
+) -> Result<Handle<SshClientHandler>> {
+    let timeout = Duration::from_secs(SSH_CONNECT_TIMEOUT_SECS);
+    let timed_out = || anyhow!("连接 {}:{} 超时", hop.host, hop.port);
+
+    // 超时只作用于建立传输通道，主机密钥确认可能需要等待用户操作
+    let result = match via {
+        None => {
+            let stream =
+                tokio::time::timeout(timeout, TcpStream::connect((hop.host.as_str(), hop.port)))
+                    .await
+                    .map_err(|_| timed_out())??;
+            let handler = SshClientHandler::new(
+                session_id,
+                &hop.host,
+                hop.port,
+                policy,
+                remote_forwards,
+                None,
+            );
+            client::connect_stream(config, stream, handler).await
+        }
+        Some(via) => {
+            let channel = tokio::time::timeout(
+                timeout,
+                via.channel_open_direct_tcpip(hop.host.as_str(), hop.port as u32, "127.0.0.1", 0),
+            )
+            .await
+            .map_err(|_| timed_out())?
+            .map_err(|e| anyhow!("上一跳无法打开到 {}:{} 的通道: {}", hop.host, hop.port, e))?;
+            let handler = SshClientHandler::new(
+                session_id,
+                &hop.host,
+                hop.port,
+                policy,
+                remote_forwards,
+                Some(via),
+            );
+            client::connect_stream(config, channel.into_stream(), handler).await
+        }
+    };
+    let mut handle = result.map_err(|e| match e {
+        russh::Error::UnknownKey => anyhow!("{}:{} 的主机密钥未被信任", hop.host, hop.port),
+        e => e.into(),
+    })?;
+
+    authenticate(&mut handle, hop).await?;
+    Ok(handle)
+}
+
+async fn authenticate(handle: &mut Handle<SshClientHandler>, hop: &SshHop) -> Result<()> {
+    let mut errors = Vec::new();
+
+    for auth in &hop.auth {
+        let result = match auth {
+            SshAuth::Password(password) => handle
+                .authenticate_password(&hop.username, password)
+                .await
+                .map(|r| r.success())
+                .map_err(anyhow::Error::from),
+            SshAuth::PublicKey { path, passphrase } => {
+                authenticate_with_key(handle, &hop.username, path, passphrase.as_deref()).await
+            }
+            SshAuth::Agent => authenticate_with_agent(handle, &hop.username).await,
+        };
+
+        match result {
+            Ok(true) => return Ok(()),
+            Ok(false) => errors.push(format!("{}: rejected", auth_label(auth))),
+            Err(e) => errors.push(format!("{}: {}", auth_label(auth), e)),
+        }
+    }
+
+    Err(anyhow!(
+        "SSH 认证失败 ({}@{}): {}",
+        hop.username,
+        hop.host,
+        errors.join("; ")
+    ))
+}
+
+async fn authenticate_with_key(
+    handle: &mut Handle<SshClientHandler>,
+    username: &str,
+    path: &Path,
+    passphrase: Option<&str>,
+) -> Result<bool> {
+    let key = load_secret_key(path, passphrase)?;
+    let hash_alg = handle.best_supported_rsa_hash().await?.flatten();
+    let result = handle
+        .authenticate_publickey(
+            username,
+            PrivateKeyWithHashAlg::new(Arc::new(key), hash_alg),
+        )
+        .await?;
+    Ok(result.success())
+}
+
+async fn authenticate_with_agent(
+    handle: &mut Handle<SshClientHandler>,
+    username: &str,
+) -> Result<bool> {
+    let mut agent = AgentClient::connect_env().await?;
+    let identities = agent.request_identities().await?;
+    let hash_alg = handle.best_supported_rsa_hash().await?.flatten();
+
+    for key in identities {
+        let result = handle
+            .authenticate_publickey_with(username, key, hash_alg, &mut agent)
+            .await
+            .map_err(|e| anyhow!("{:?}", e))?;
+        if result.success() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn auth_label(auth: &SshAuth) -> String {
+    match auth {
+        SshAuth::Password(_) => "password".to_string(),
+        SshAuth::PublicKey { path, .. } => format!("publickey {}", path.display()),
+        SshAuth::Agent => "agent".to_string(),
+    }
+}
+
+/// 连接、认证并打开交互式 shell，输出通过 `terminal-output-{id}` 事件推送，
+/// 连接状态通过 `ssh-state-{id}` 事件推送
+///
+/// 返回的 JoinHandle 在远程 shell 退出、连接断开且重连失败时完成
+pub async fn open_shell(
+    session_id: u64,
+    options: &SshConnectOptions,
+    prompts: HostKeyPrompts,
+    output: OutputSink,
+    app_handle: AppHandle,
+) -> Result<(SshChannelHandle, JoinHandle<TerminalExit>)> {
+    let policy = HostKeyPolicy::new(
+        options.host_key_checking,
+        app_handle.clone(),
+        prompts,
+        HostKeyPromptKind::Terminal,
+    );
+    let remote_forwards = RemoteForwards::default();
+    emit_state(&app_handle, session_id, SshState::Connecting, None, None);
+    let (handle, channel) =
+        match start_shell(session_id, options, policy.clone(), remote_forwards.clone()).await {
+            Ok(connected) => connected,
+            Err(e) => {
+                let error = Some(e.to_string());
+                emit_state(&app_handle, session_id, SshState::Failed, None, error);
+                return Err(e);
+            }
+        };
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    for command in startup_commands(options, false) {
+        let _ = tx.send(SshInput::Data(format!("{}\n", command).into_bytes()));
+    }
+
+    // 转发失败不影响终端会话，状态通过 ssh-forward-{id} 事件推送
+    let forwards = SshForwards::new(
+        session_id,
+        Arc::clone(&handle),
+        remote_forwards.clone(),
+        app_handle.clone(),
+    );
+    for spec in &options.forwards {
+        if let Err(e) = forwards.add(spec.clone()).await {
+            eprintln!("Invalid port forward for SSH session {}: {}", session_id, e);
+        }
+    }
+    emit_state(&app_handle, session_id, SshState::Connected, None, None);
+
+    let session = SshSession {
+        session_id,
+        options: options.clone(),
+        policy,
+        remote_forwards,
+        handle,
+        channel,
+        rx,
+        output,
+        forwards: forwards.clone(),
+        app_handle,
+    };
+    let task = tokio::spawn(session.run());
+
+    Ok((SshChannelHandle { tx, forwards }, task))
+}
+
+// 连接并在新通道上打开带 PTY 的 shell，首次连接和重连共用
+async fn start_shell(
+    session_id: u64,
+    options: &SshConnectOptions,
+    policy: HostKeyPolicy,
+    remote_forwards: RemoteForwards,
+) -> Result<(Arc<Handle<SshClientHandler>>, Channel<Msg>)> {
+    let handle = Arc::new(connect(session_id, options, policy, remote_forwards).await?);
+
+    let channel = handle.channel_open_session().await?;
+    for (name, value) in &options.env {
+        if let Err(e) = channel.set_env(false, name.as_str(), value.as_str()).await {
+            eprintln!("Failed to set remote env {}: {}", name, e);
+        }
+    }
+    channel
+        .request_pty(
+            false,
+            "xterm-256color",
+            options.cols as u32,
+            options.rows as u32,
+            0,
+            0,
+            &[],
+        )
+        .await?;
+    channel.request_shell(false).await?;
+    Ok((handle, channel))
+}
+
+// shell 打开后依次执行的命令：startup_command（重连时按设置决定是否执行），
+// 然后挂载 tmux 会话
+fn startup_commands(options: &SshConnectOptions, reconnected: bool) -> Vec<String> {
+    let mut commands = Vec::new();
+    if !reconnected || options.reconnect.rerun_startup_command {
+        commands.extend(options.startup_command.clone());
+    }
+    if let Some(name) = &options.reconnect.tmux_session {
+        commands.push(format!("tmux new-session -A -s {}", shell_quote(name)));
+    }
+    commands
+}
+
+// 用单引号包围，内部的单引号写成 '\''
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+fn emit_state(
+    app_handle: &AppHandle,
+    session_id: u64,
+    state: SshState,
+    attempt: Option<u32>,
+    error: Option<String>,
+) {
+    let event = SshStateEvent {
+        session_id,
+        state,
+        attempt,
+        delay_ms: None,
+        error,
+    };
+    emit_state_event(app_handle, event);
+}
+
+fn emit_state_event(app_handle: &AppHandle, event: SshStateEvent) {
+    if let Err(e) = app_handle.emit(&format!("ssh-state-{}", event.session_id), event) {
+        eprintln!("Failed to emit SSH state: {}", e);
+    }
+}
+
+/// 连接、认证并打开 sftp 子系统，供远程文件浏览使用
+///
+/// 主机密钥提示与终端共用，以 `HostKeyPromptKind::Sftp` 区分，`session_id` 为 SFTP 连接编号
+pub async fn open_sftp(
+    session_id: u64,
+    options: &SshConnectOptions,
+    prompts: HostKeyPrompts,
+    app_handle: AppHandle,
+) -> Result<(Handle<SshClientHandler>, SftpSession)> {
+    let policy = HostKeyPolicy::new(
+        options.host_key_checking,
+        app_handle,
+        prompts,
+        HostKeyPromptKind::Sftp,
+    );
+    let handle = connect(session_id, options, policy, RemoteForwards::default()).await?;
+
+    let channel = handle.channel_open_session().await?;
+    channel.request_subsystem(true, "sftp").await?;
+    let sftp = SftpSession::new(channel.into_stream())
+        .await
+        .map_err(|e| anyhow!("无法启动 SFTP 会话: {}", e))?;
+    Ok((handle, sftp))
+}
+
+// 通道结束的原因
+enum ChannelEnd {
+    /// 远程 shell 退出或用户关闭会话
+    Closed,
+    /// 连接意外断开（保活超时、网络中断等）
+    Lost(String),
+}
+
+// SSH 会话任务，连接断开时按 SshReconnect 设置重连并替换连接和通道
+struct SshSession {
+    session_id: u64,
+    options: SshConnectOptions,
+    policy: HostKeyPolicy,
+    remote_forwards: RemoteForwards,
+    handle: Arc<Handle<SshClientHandler>>,
+    channel: Channel<Msg>,
+    rx: mpsc::UnboundedReceiver<SshInput>,
+    output: OutputSink,
+    forwards: SshForwards,
+    app_handle: AppHandle,
+}
+
+impl SshSession {
+    async fn run(mut self) -> TerminalExit {
+        let mut exit = TerminalExit::default();
+        loop {
+            match self.pump(&mut exit).await {
+                ChannelEnd::Closed => break,
+                ChannelEnd::Lost(reason) => {
+                    eprintln!("SSH session {} lost: {}", self.session_id, reason);
+                    self.notice(&format!("SSH 连接已断开: {}", reason));
+                    if !self.options.reconnect.enabled {
+                        self.emit(SshState::Failed, None, None, Some(reason));
+                        break;
+                    }
+                    if !self.reconnect(reason).await {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let _ = self.output.flush();
+        self.forwards.shutdown();
+        let _ = self
+            .handle
+            .disconnect(Disconnect::ByApplication, "", "en")
+            .await;
+        exit
+    }
+
+    // 主循环：转发输入、调整窗口大小、推送输出
+    async fn pump(&mut self, exit: &mut TerminalExit) -> ChannelEnd {
+        let session_id = self.session_id;
+        // 与本地 PTY 相同，输出按帧合并后推送
+        let mut flush_interval = tokio::time::interval(Duration::from_millis(OUTPUT_BUFFER_MS));
+        // 服务端先发送 EOF，随后才是 exit-status / exit-signal 和 Close
+        let mut eof = false;
+        loop {
+            tokio::select! {
+                _ = flush_interval.tick() => {
+                    if let Err(e) = self.output.flush() {
+                        eprintln!("Failed to emit SSH output for session {}: {}", session_id, e);
+                        return ChannelEnd::Closed;
+                    }
+                }
+                input = self.rx.recv() => match input {
+                    Some(SshInput::Data(data)) => {
+                        if let Err(e) = self.channel.data(&data[..]).await {
+                            eprintln!("Failed to write to SSH session {}: {}", session_id, e);
+                            return ChannelEnd::Lost(e.to_string());
+                        }
+                    }
+                    Some(SshInput::Resize { cols, rows }) => {
+                        // 记下窗口大小，重连时按当前大小申请 PTY
+                        self.options.cols = cols;
+                        self.options.rows = rows;
+                        if let Err(e) = self.channel.window_change(cols as u32, rows as u32, 0, 0).await {
+                            eprintln!("Failed to resize SSH session {}: {}", session_id, e);
+                        }
+                    }
+                    Some(SshInput::Close) | None => {
+                        let _ = self.channel.eof().await;
+                        return ChannelEnd::Closed;
+                    }
+                },
+                msg = self.channel.wait() => match msg {
+                    Some(ChannelMsg::Data { data }) | Some(ChannelMsg::ExtendedData { data, .. }) => {
+                        if let Err(e) = self.output.push(&data) {
+                            eprintln!("Failed to emit SSH output for session {}: {}", session_id, e);
+                            return ChannelEnd::Closed;
+                        }
+                    }
+                    Some(ChannelMsg::ExitStatus { exit_status }) => {
+                        exit.code = Some(exit_status);
+                    }
+                    Some(ChannelMsg::ExitSignal { signal_name, .. }) => {
+                        exit.signal = Some(format!("{:?}", signal_name));
+                    }
+                    Some(ChannelMsg::Eof) => eof = true,
+                    Some(ChannelMsg::Close) => {
+                        println!("SSH session {} closed", session_id);
+                        return ChannelEnd::Closed;
+                    }
+                    // 通道没有正常关闭就结束，说明连接已断开
+                    None if !eof && exit.code.is_none() && exit.signal.is_none() => {
+                        return ChannelEnd::Lost("连接已中断".to_string());
+                    }
+                    None => return ChannelEnd::Closed,
+                    Some(_) => {}
+                },
+            }
+        }
+    }
+
+    // 按指数退避重连，成功后恢复端口转发并执行启动命令；返回是否已重新连接
+    async fn reconnect(&mut self, mut error: String) -> bool {
+        let max_attempts = self.options.reconnect.max_attempts;
+        let mut attempt = 0;
+        while max_attempts == 0 || attempt < max_attempts {
+            attempt += 1;
+            let delay = self.options.reconnect.delay(attempt);
+            self.emit(
+                SshState::Reconnecting,
+                Some(attempt),
+                Some(delay),
+                Some(error.clone()),
+            );
+            if !self.wait_for_retry(delay).await {
+                return false;
+            }
+
+            // 连接期间（包括等待主机密钥确认时）也要响应关闭请求
+            let mut size = None;
+            let started = {
+                let connecting = start_shell(
+                    self.session_id,
+                    &self.options,
+                    self.policy.clone(),
+                    self.remote_forwards.clone(),
+                );
+                tokio::pin!(connecting);
+                loop {
+                    tokio::select! {
+                        started = &mut connecting => break Some(started),
+                        input = self.rx.recv() => match input {
+                            Some(SshInput::Data(_)) => {}
+                            Some(SshInput::Resize { cols, rows }) => size = Some((cols, rows)),
+                            Some(SshInput::Close) | None => break None,
+                        },
+                    }
+                }
+            };
+            let Some(started) = started else {
+                // 放弃本次连接，未应答的主机密钥提示随之作废
+                if let HostKeyPolicy::Prompt { prompts, kind, .. } = &self.policy {
+                    prompts.cancel(*kind, self.session_id);
+                }
+                return false;
+            };
+            if let Some((cols, rows)) = size {
+                self.options.cols = cols;
+                self.options.rows = rows;
+            }
+
+            match started {
+                Ok((handle, channel)) => {
+                    self.handle = handle;
+                    self.channel = channel;
+                    // 连接期间窗口大小有变化，按最新大小调整
+                    if size.is_some() {
+                        let (cols, rows) = (self.options.cols as u32, self.options.rows as u32);
+                        if let Err(e) = self.channel.window_change(cols, rows, 0, 0).await {
+                            eprintln!("Failed to resize SSH session {}: {}", self.session_id, e);
+                        }
+                    }
+                    self.forwards.reconnect(Arc::clone(&self.handle)).await;
+                    for command in startup_commands(&self.options, true) {
+                        let input = format!("{}\n", command);
+                        if let Err(e) = self.channel.data(input.as_bytes()).await {
+                            eprintln!("Failed to write to SSH session {}: {}", self.session_id, e);
+                        }
+                    }
+                    println!(
+                        "SSH session {} reconnected after {} attempt(s)",
+                        self.session_id, attempt
+                    );
+                    self.notice("已重新连接");
+                    self.emit(SshState::Connected, None, None, None);
+                    return true;
+                }
+                Err(e) => {
+                    eprintln!(
+                        "Reconnect attempt {} for SSH session {} failed: {}",
+                        attempt, self.session_id, e
+                    );
+                    error = e.to_string();
+                }
+            }
+        }
+
+        self.notice(&format!("重连失败: {}", error));
+        self.emit(SshState::Failed, Some(attempt), None, Some(error));
+        false
+    }
+
+    // 等待下一次重连；期间丢弃输入、记下窗口大小，收到关闭请求时返回 false
+    async fn wait_for_retry(&mut self, delay: Duration) -> bool {
+        let sleep = tokio::time::sleep(delay);
+        tokio::pin!(sleep);
+        loop {
+            tokio::select! {
+                _ = &mut sleep => return true,
+                input = self.rx.recv() => match input {
+                    Some(SshInput::Data(_)) => {}
+                    Some(SshInput::Resize { cols, rows }) => {
+                        self.options.cols = cols;
+                        self.options.rows = rows;
+                    }
+                    Some(SshInput::Close) | None => return false,
+                },
+            }
+        }
+    }
+
+    // 在终端中显示一行提示
+    fn notice(&self, message: &str) {
+        let line = format!("\r\n\x1b[33m[{}]\x1b[0m\r\n", message);
+        if let Err(e) = self.output.push(line.as_bytes()) {
+            eprintln!(
+                "Failed to emit SSH output for session {}: {}",
+                self.session_id, e
+            );
+        }
+    }
+
+    fn emit(
+        &self,
+        state: SshState,
+        attempt: Option<u32>,
+        delay: Option<Duration>,
+        error: Option<String>,
+    ) {
+        let event = SshStateEvent {
+            session_id: self.session_id,
+            state,
+            attempt,
+            delay_ms: delay.map(|delay| delay.as_millis() as u64),
+            error,
+        };
+        emit_state_event(&self.app_handle, event);
+    }
+}
+
+// 展开 ~ 为用户主目录
+fn expand_home(path: &str) -> String {
+    if path == "~" || path.starts_with("~/") {
+        if let Ok(home) = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")) {
+            return path.replacen('~', &home, 1);
+        }
+    }
+    path.to_string()
+}
+
+fn default_known_hosts_path() -> PathBuf {
+    PathBuf::from(expand_home("~/.ssh/known_hosts"))
+}
+
+// 删除 known_hosts 中指定的行（行号从 1 开始）
+fn remove_known_hosts_line(path: &Path, line: usize) -> std::io::Result<()> {
+    let content = std::fs::read_to_string(path)?;
+    let kept: Vec<&str> = content
+        .lines()
+        .enumerate()
+        .filter(|(i, _)| i + 1 != line)
+        .map(|(_, l)| l)
+        .collect();
+    let mut output = kept.join("\n");
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    std::fs::write(path, output)
+}
+
+// OpenSSH 默认尝试的私钥文件
+fn default_identity_files() -> Vec<PathBuf> {
+    let Ok(home) = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")) else {
+        return Vec::new();
+    };
+    let ssh_dir = PathBuf::from(home).join(".ssh");
+    ["id_ed25519", "id_ecdsa", "id_rsa"]
+        .iter()
+        .map(|name| ssh_dir.join(name))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auth_selection() {
+        let options = SshConnectOptions::new(
+            "example.com".to_string(),
+            22,
+            "root".to_string(),
+            Some("secret".to_string()),
+            None,
+            None,
+        );
+        assert!(matches!(options.auth.as_slice(), [SshAuth::Password(p)] if p == "secret"));
+
+        let options = SshConnectOptions::new(
+            "example.com".to_string(),
+            22,
+            "root".to_string(),
+            None,
+            Some("/tmp/id_test".to_string()),
+            None,
+        );
+        assert!(matches!(
+            options.auth.as_slice(),
+            [SshAuth::PublicKey { path, .. }] if path == &PathBuf::from("/tmp/id_test")
+        ));
+
+        let options = SshConnectOptions::new(
+            "example.com".to_string(),
+            22,
+            "root".to_string(),
+            None,
+            None,
+            None,
+        );
+        assert!(matches!(options.auth.first(), Some(SshAuth::Agent)));
+    }
+
+    #[test]
+    fn test_parse_jump_host() {
+        let hop = SshHop::parse("bastion", "deploy").unwrap();
+        assert_eq!(hop.label(), "deploy@bastion:22");
+        assert!(matches!(hop.auth.first(), Some(SshAuth::Agent)));
+
+        let hop = SshHop::parse(" ops@jump.example.com:2222 ", "deploy").unwrap();
+        assert_eq!(hop.label(), "ops@jump.example.com:2222");
+
+        let hop = SshHop::parse("root@[fd00::1]:2200", "deploy").unwrap();
+        assert_eq!((hop.host.as_str(), hop.port), ("fd00::1", 2200));
+
+        assert!(SshHop::parse("bastion:ssh", "deploy").is_err());
+        assert!(SshHop::parse("ops@", "deploy").is_err());
+        assert!(SshHop::parse("[::1", "deploy").is_err());
+    }
+
+    #[test]
+    fn test_reconnect_backoff() {
+        let reconnect = SshReconnect::default();
+        let delays: Vec<_> = (1..=7)
+            .map(|attempt| reconnect.delay(attempt).as_secs())
+            .collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 30, 30]);
+        assert_eq!(reconnect.delay(u32::MAX), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_startup_commands() {
+        let mut options =
+            SshConnectOptions::new("h".to_string(), 22, "u".to_string(), None, None, None);
+        options.startup_command = Some("cd /srv/app".to_string());
+        options.reconnect.tmux_session = Some("it's main".to_string());
+
+        let attach = r"tmux new-session -A -s 'it'\''s main'";
+        assert_eq!(
+            startup_commands(&options, false),
+            vec!["cd /srv/app", attach]
+        );
+        assert_eq!(startup_commands(&options, true), vec![attach]);
+
+        options.reconnect.rerun_startup_command = true;
+        assert_eq!(startup_commands(&options, true).len(), 2);
+    }
+
+    #[test]
+    fn test_remove_known_hosts_line() {
+        let path = std::env::temp_dir().join(format!("huaan_known_hosts_{}", std::process::id()));
+        std::fs::write(
+            &path,
+            "a ssh-ed25519 AAAA\nb ssh-ed25519 BBBB\nc ssh-rsa CCCC\n",
+        )
+        .unwrap();
+        remove_known_hosts_line(&path, 2).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "a ssh-ed25519 AAAA\nc ssh-rsa CCCC\n"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_host_key_prompt_respond() {
+        let prompts = HostKeyPrompts::new();
+        assert!(prompts
+            .respond(HostKeyPromptKind::Terminal, 1, true)
+            .is_err());
+
+        let mut rx = prompts.register(HostKeyPromptKind::Terminal, 1);
+        let mut sftp_rx = prompts.register(HostKeyPromptKind::Sftp, 1);
+        prompts
+            .respond(HostKeyPromptKind::Terminal, 1, true)
+            .unwrap();
+        assert_eq!(rx.try_recv(), Ok(true));
+        assert!(prompts
+            .respond(HostKeyPromptKind::Terminal, 1, false)
+            .is_err());
+
+        // 编号相同的 SFTP 连接有自己的提示
+        assert!(sftp_rx.try_recv().is_err());
+        prompts.respond(HostKeyPromptKind::Sftp, 1, false).unwrap();
+        assert_eq!(sftp_rx.try_recv(), Ok(false));
+    }
+
+    // 需要一个可访问的 sshd，例如：
+    //   docker compose --profile test up -d sshd
+    //   HUAAN_TEST_SSH_HOST=127.0.0.1 HUAAN_TEST_SSH_PORT=2222 \
+    //   HUAAN_TEST_SSH_USER=huaan HUAAN_TEST_SSH_PASSWORD=huaan \
+    //   cargo test -- --ignored ssh
+    #[tokio::test]
+    #[ignore]
+    async fn test_password_login_against_local_sshd() {
+        let host = std::env::var("HUAAN_TEST_SSH_HOST").unwrap_or_else(|_| "127.0.0.1".into());
+        let port = std::env::var("HUAAN_TEST_SSH_PORT")
+            .ok()
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(2222);
+        let user = std::env::var("HUAAN_TEST_SSH_USER").unwrap_or_else(|_| "huaan".into());
+        let password = std::env::var("HUAAN_TEST_SSH_PASSWORD").unwrap_or_else(|_| "huaan".into());
+
+        let options = SshConnectOptions::new(host, port, user, Some(password), None, None);
+        let handle = connect(
+            0,
+            &options,
+            HostKeyPolicy::AcceptNew,
+            RemoteForwards::default(),
+        )
+        .await
+        .expect("ssh connect failed");
+
+        let mut channel = handle.channel_open_session().await.unwrap();
+        channel.exec(true, "echo huaan-ssh-ok").await.unwrap();
+
+        let mut output = Vec::new();
+        while let Some(msg) = channel.wait().await {
+            match msg {
+                ChannelMsg::Data { data } => output.extend_from_slice(&data),
+                ChannelMsg::ExitStatus { exit_status } => {
+                    assert_eq!(exit_status, 0);
+                }
+                ChannelMsg::Close => break,
+                _ => {}
+            }
+        }
+        assert!(String::from_utf8_lossy(&output).contains("huaan-ssh-ok"));
+    }
+}
@@ -0,0 +1,667 @@
+use crate::ssh::SshClientHandler;
+use anyhow::{anyhow, Result};
+use russh::client::{Handle, Msg};
+use russh::Channel;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// 端口转发类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForwardKind {
+    /// `-L`：本地端口经 SSH 连接到远程可访问的目标
+    Local,
+    /// `-R`：服务端端口经 SSH 连接回本地可访问的目标
+    Remote,
+    /// `-D`：本地 SOCKS5 代理
+    Dynamic,
+}
+
+/// 一条端口转发配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForwardSpec {
+    pub kind: ForwardKind,
+    /// 监听地址，远程转发时为服务端上的地址
+    #[serde(default = "default_bind_address")]
+    pub bind_address: String,
+    /// 为 0 时由系统（远程转发时由服务端）分配端口
+    pub bind_port: u16,
+    /// 转发目标，动态转发时为空
+    #[serde(default)]
+    pub target_host: Option<String>,
+    #[serde(default)]
+    pub target_port: Option<u16>,
+}
+
+fn default_bind_address() -> String {
+    "127.0.0.1".to_string()
+}
+
+impl ForwardSpec {
+    /// 解析 OpenSSH 命令行格式
+    ///
+    /// - `-L` / `-R`：`[bind_address:]port:host:hostport`
+    /// - `-D`：`[bind_address:]port`
+    ///
+    /// IPv6 地址用方括号包围，例如 `[::1]:8080:localhost:80`
+    pub fn parse(kind: ForwardKind, spec: &str) -> Result<Self> {
+        let parts = split_forward_spec(spec);
+        let parse_port = |port: &str| -> Result<u16> {
+            port.parse()
+                .map_err(|_| anyhow!("端口转发 '{}' 中的端口无效: {}", spec, port))
+        };
+
+        let (bind, target) = match (kind, parts.as_slice()) {
+            (ForwardKind::Dynamic, [port]) => ((None, port), None),
+            (ForwardKind::Dynamic, [address, port]) => ((Some(address), port), None),
+            (ForwardKind::Local | ForwardKind::Remote, [port, host, host_port]) => {
+                ((None, port), Some((host, host_port)))
+            }
+            (ForwardKind::Local | ForwardKind::Remote, [address, port, host, host_port]) => {
+                ((Some(address), port), Some((host, host_port)))
+            }
+            _ => return Err(anyhow!("无法解析端口转发 '{}'", spec)),
+        };
+
+        let forward = Self {
+            kind,
+            bind_address: bind
+                .0
+                .filter(|address| !address.is_empty())
+                .cloned()
+                .unwrap_or_else(default_bind_address),
+            bind_port: parse_port(bind.1)?,
+            target_host: target.map(|(host, _)| host.clone()),
+            target_port: target.map(|(_, port)| parse_port(port)).transpose()?,
+        };
+        forward.validate()?;
+        Ok(forward)
+    }
+
+    /// 检查本地和远程转发是否指定了目标
+    pub fn validate(&self) -> Result<()> {
+        if self.kind != ForwardKind::Dynamic {
+            self.target()?;
+        }
+        Ok(())
+    }
+
+    fn target(&self) -> Result<(String, u16)> {
+        match (&self.target_host, self.target_port) {
+            (Some(host), Some(port)) if !host.is_empty() && port != 0 => Ok((host.clone(), port)),
+            _ => Err(anyhow!("端口转发缺少目标地址")),
+        }
+    }
+}
+
+// 按冒号拆分，方括号内的冒号（IPv6 地址）不拆分
+fn split_forward_spec(spec: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut in_brackets = false;
+    for ch in spec.trim().chars() {
+        match ch {
+            '[' => in_brackets = true,
+            ']' => in_brackets = false,
+            ':' if !in_brackets => parts.push(String::new()),
+            _ => parts.last_mut().unwrap().push(ch),
+        }
+    }
+    parts
+}
+
+/// 端口转发状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForwardState {
+    Active,
+    /// 监听失败（端口被占用、服务端拒绝等），原因见 error
+    Failed,
+    Closed,
+}
+
+/// 端口转发状态，也是 `ssh-forward-{session_id}` 事件的负载
+#[derive(Debug, Clone, Serialize)]
+pub struct ForwardStatus {
+    pub session_id: u64,
+    pub id: u64,
+    #[serde(flatten)]
+    pub spec: ForwardSpec,
+    /// 实际监听的端口
+    pub bound_port: Option<u16>,
+    pub state: ForwardState,
+    pub error: Option<String>,
+}
+
+/// 远程转发的本地目标，按服务端监听端口索引
+///
+/// 服务端收到连接时会打开 forwarded-tcpip 通道，由 SshClientHandler 通过它找到目标
+#[derive(Clone, Default)]
+pub struct RemoteForwards {
+    targets: Arc<Mutex<HashMap<u32, (String, u16)>>>,
+}
+
+impl RemoteForwards {
+    fn insert(&self, port: u32, target: (String, u16)) {
+        self.targets.lock().unwrap().insert(port, target);
+    }
+
+    fn remove(&self, port: u32) {
+        self.targets.lock().unwrap().remove(&port);
+    }
+
+    /// 把服务端打开的通道连接到对应的本地目标
+    pub fn accept(&self, channel: Channel<Msg>, connected_port: u32) {
+        let Some((host, port)) = self.targets.lock().unwrap().get(&connected_port).cloned() else {
+            eprintln!("No remote forward registered for port {}", connected_port);
+            return;
+        };
+        tokio::spawn(async move {
+            match TcpStream::connect((host.as_str(), port)).await {
+                Ok(mut socket) => {
+                    let mut stream = channel.into_stream();
+                    let _ = tokio::io::copy_bidirectional(&mut socket, &mut stream).await;
+                }
+                Err(e) => {
+                    eprintln!("Remote forward to {}:{} failed: {}", host, port, e);
+                    let _ = channel.close().await;
+                }
+            }
+        });
+    }
+}
+
+struct Forward {
+    status: ForwardStatus,
+    // 本地监听任务，远程转发时为空
+    listener: Option<JoinHandle<()>>,
+}
+
+/// 一个 SSH 连接上的全部端口转发
+#[derive(Clone)]
+pub struct SshForwards {
+    session_id: u64,
+    // 重连后替换为新连接，本地监听在下一个连接到来时使用新连接
+    handle: Arc<Mutex<Arc<Handle<SshClientHandler>>>>,
+    remote: RemoteForwards,
+    app_handle: AppHandle,
+    next_id: Arc<AtomicU64>,
+    forwards: Arc<Mutex<HashMap<u64, Forward>>>,
+}
+
+impl SshForwards {
+    pub fn new(
+        session_id: u64,
+        handle: Arc<Handle<SshClientHandler>>,
+        remote: RemoteForwards,
+        app_handle: AppHandle,
+    ) -> Self {
+        Self {
+            session_id,
+            handle: Arc::new(Mutex::new(handle)),
+            remote,
+            app_handle,
+            next_id: Arc::new(AtomicU64::new(1)),
+            forwards: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn list(&self) -> Vec<ForwardStatus> {
+        let mut list: Vec<_> = self
+            .forwards
+            .lock()
+            .unwrap()
+            .values()
+            .map(|forward| forward.status.clone())
+            .collect();
+        list.sort_by_key(|status| status.id);
+        list
+    }
+
+    /// 建立转发；监听失败时转发以 failed 状态保留在列表中，并推送状态事件
+    pub async fn add(&self, spec: ForwardSpec) -> Result<ForwardStatus> {
+        spec.validate()?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let started = match spec.kind {
+            ForwardKind::Local | ForwardKind::Dynamic => self.listen_local(&spec).await,
+            ForwardKind::Remote => self.listen_remote(&spec).await.map(|port| (port, None)),
+        };
+
+        let (status, listener) = match started {
+            Ok((port, listener)) => (self.status(id, spec, Some(port), None), listener),
+            Err(e) => {
+                eprintln!(
+                    "Port forward {} of SSH session {} failed: {}",
+                    id, self.session_id, e
+                );
+                (self.status(id, spec, None, Some(e.to_string())), None)
+            }
+        };
+        self.emit(&status);
+        self.forwards.lock().unwrap().insert(
+            id,
+            Forward {
+                status: status.clone(),
+                listener,
+            },
+        );
+        Ok(status)
+    }
+
+    /// 关闭并删除转发
+    ///
+    /// 远程转发需要服务端先取消监听；取消失败时转发仍保留在列表中，状态事件带上错误原因
+    pub async fn remove(&self, id: u64) -> Result<()> {
+        let status = self
+            .forwards
+            .lock()
+            .unwrap()
+            .get(&id)
+            .map(|forward| forward.status.clone())
+            .ok_or_else(|| anyhow!("端口转发 {} 不存在", id))?;
+
+        if let (ForwardKind::Remote, ForwardState::Active, Some(port)) =
+            (status.spec.kind, status.state, status.bound_port)
+        {
+            let cancelled = self
+                .handle()
+                .cancel_tcpip_forward(status.spec.bind_address.as_str(), port as u32)
+                .await;
+            if let Err(e) = cancelled {
+                let error = format!("无法取消远程转发: {}", e);
+                let mut forwards = self.forwards.lock().unwrap();
+                if let Some(forward) = forwards.get_mut(&id) {
+                    forward.status.error = Some(error.clone());
+                    self.emit(&forward.status);
+                }
+                return Err(anyhow!(error));
+            }
+            self.remote.remove(port as u32);
+        }
+
+        let Some(forward) = self.forwards.lock().unwrap().remove(&id) else {
+            return Ok(());
+        };
+        if let Some(listener) = forward.listener {
+            listener.abort();
+        }
+        let mut status = forward.status;
+        status.state = ForwardState::Closed;
+        self.emit(&status);
+        Ok(())
+    }
+
+    /// 重连后切换到新连接：本地监听保持不变，远程转发重新向服务端申请
+    pub async fn reconnect(&self, handle: Arc<Handle<SshClientHandler>>) {
+        *self.handle.lock().unwrap() = handle;
+
+        let remotes: Vec<ForwardStatus> = self
+            .forwards
+            .lock()
+            .unwrap()
+            .values()
+            .map(|forward| forward.status.clone())
+            .filter(|status| {
+                status.spec.kind == ForwardKind::Remote && status.state == ForwardState::Active
+            })
+            .collect();
+        for previous in remotes {
+            if let Some(port) = previous.bound_port {
+                self.remote.remove(port as u32);
+            }
+            let status = match self.listen_remote(&previous.spec).await {
+                Ok(port) => self.status(previous.id, previous.spec, Some(port), None),
+                Err(e) => {
+                    eprintln!(
+                        "Port forward {} of SSH session {} failed after reconnect: {}",
+                        previous.id, self.session_id, e
+                    );
+                    self.status(previous.id, previous.spec, None, Some(e.to_string()))
+                }
+            };
+            self.emit(&status);
+            if let Some(forward) = self.forwards.lock().unwrap().get_mut(&status.id) {
+                forward.status = status;
+            }
+        }
+    }
+
+    /// 连接断开时停止所有本地监听
+    pub fn shutdown(&self) {
+        for (_, forward) in self.forwards.lock().unwrap().drain() {
+            if let Some(listener) = forward.listener {
+                listener.abort();
+            }
+        }
+    }
+
+    async fn listen_local(&self, spec: &ForwardSpec) -> Result<(u16, Option<JoinHandle<()>>)> {
+        // 与 OpenSSH 一致，`*` 表示监听所有地址
+        let address = match spec.bind_address.as_str() {
+            "*" => "0.0.0.0",
+            address => address,
+        };
+        let listener = TcpListener::bind((address, spec.bind_port))
+            .await
+            .map_err(|e| anyhow!("无法监听 {}:{}: {}", spec.bind_address, spec.bind_port, e))?;
+        let port = listener.local_addr()?.port();
+        let target = match spec.kind {
+            ForwardKind::Dynamic => None,
+            _ => Some(spec.target()?),
+        };
+
+        let shared = Arc::clone(&self.handle);
+        let task = tokio::spawn(async move {
+            loop {
+                let (socket, peer) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        eprintln!("Port forward on {} stopped accepting: {}", port, e);
+                        break;
+                    }
+                };
+                let handle = Arc::clone(&shared.lock().unwrap());
+                let target = target.clone();
+                tokio::spawn(async move {
+                    let originator = (peer.ip().to_string(), peer.port() as u32);
+                    let result = match target {
+                        Some(target) => {
+                            forward_connection(&handle, socket, target, originator).await
+                        }
+                        None => socks_connection(&handle, socket, originator).await,
+                    };
+                    if let Err(e) = result {
+                        eprintln!("Forwarded connection from {} failed: {}", peer, e);
+                    }
+                });
+            }
+        });
+        Ok((port, Some(task)))
+    }
+
+    async fn listen_remote(&self, spec: &ForwardSpec) -> Result<u16> {
+        let target = spec.target()?;
+        let assigned = self
+            .handle()
+            .tcpip_forward(spec.bind_address.as_str(), spec.bind_port as u32)
+            .await
+            .map_err(|e| {
+                anyhow!(
+                    "服务端拒绝监听 {}:{}: {}",
+                    spec.bind_address,
+                    spec.bind_port,
+                    e
+                )
+            })?;
+        // 请求端口为 0 时服务端返回实际分配的端口
+        let port = if spec.bind_port == 0 {
+            u16::try_from(assigned).map_err(|_| anyhow!("服务端分配的端口无效: {}", assigned))?
+        } else {
+            spec.bind_port
+        };
+        self.remote.insert(port as u32, target);
+        Ok(port)
+    }
+
+    fn handle(&self) -> Arc<Handle<SshClientHandler>> {
+        Arc::clone(&self.handle.lock().unwrap())
+    }
+
+    fn status(
+        &self,
+        id: u64,
+        spec: ForwardSpec,
+        bound_port: Option<u16>,
+        error: Option<String>,
+    ) -> ForwardStatus {
+        ForwardStatus {
+            session_id: self.session_id,
+            id,
+            spec,
+            bound_port,
+            state: if error.is_some() {
+                ForwardState::Failed
+            } else {
+                ForwardState::Active
+            },
+            error,
+        }
+    }
+
+    fn emit(&self, status: &ForwardStatus) {
+        if let Err(e) = self
+            .app_handle
+            .emit(&format!("ssh-forward-{}", self.session_id), status)
+        {
+            eprintln!("Failed to emit port forward status: {}", e);
+        }
+    }
+}
+
+// 通过 direct-tcpip 通道把本地连接转发到目标
+async fn forward_connection(
+    handle: &Handle<SshClientHandler>,
+    mut socket: TcpStream,
+    (host, port): (String, u16),
+    (originator_address, originator_port): (String, u32),
+) -> Result<()> {
+    let channel = handle
+        .channel_open_direct_tcpip(host, port as u32, originator_address, originator_port)
+        .await?;
+    let mut stream = channel.into_stream();
+    tokio::io::copy_bidirectional(&mut socket, &mut stream).await?;
+    Ok(())
+}
+
+// SOCKS5 代理连接：读取目标地址后按 direct-tcpip 转发
+async fn socks_connection(
+    handle: &Handle<SshClientHandler>,
+    mut socket: TcpStream,
+    (originator_address, originator_port): (String, u32),
+) -> Result<()> {
+    let (host, port) = socks5_request(&mut socket).await?;
+    let channel = match handle
+        .channel_open_direct_tcpip(
+            host.as_str(),
+            port as u32,
+            originator_address,
+            originator_port,
+        )
+        .await
+    {
+        Ok(channel) => channel,
+        Err(e) => {
+            // 0x04：主机不可达
+            socks5_reply(&mut socket, 0x04).await?;
+            return Err(anyhow!("无法连接 {}:{}: {}", host, port, e));
+        }
+    };
+    socks5_reply(&mut socket, 0x00).await?;
+    let mut stream = channel.into_stream();
+    tokio::io::copy_bidirectional(&mut socket, &mut stream).await?;
+    Ok(())
+}
+
+// 完成 SOCKS5 握手并读取 CONNECT 请求的目标地址，只支持无认证和 CONNECT
+async fn socks5_request<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+) -> io::Result<(String, u16)> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    let mut greeting = [0u8; 2];
+    stream.read_exact(&mut greeting).await?;
+    if greeting[0] != 5 {
+        return Err(invalid("unsupported SOCKS version"));
+    }
+    let mut methods = vec![0u8; greeting[1] as usize];
+    stream.read_exact(&mut methods).await?;
+    if !methods.contains(&0) {
+        stream.write_all(&[5, 0xff]).await?;
+        return Err(invalid("no acceptable SOCKS authentication method"));
+    }
+    stream.write_all(&[5, 0]).await?;
+
+    let mut request = [0u8; 4];
+    stream.read_exact(&mut request).await?;
+    if request[1] != 1 {
+        // 0x07：不支持的命令
+        socks5_reply(stream, 0x07).await?;
+        return Err(invalid("only SOCKS CONNECT is supported"));
+    }
+    let host = match request[3] {
+        1 => {
+            let mut ip = [0u8; 4];
+            stream.read_exact(&mut ip).await?;
+            Ipv4Addr::from(ip).to_string()
+        }
+        3 => {
+            let mut name = vec![0u8; stream.read_u8().await? as usize];
+            stream.read_exact(&mut name).await?;
+            String::from_utf8(name).map_err(|_| invalid("invalid SOCKS domain name"))?
+        }
+        4 => {
+            let mut ip = [0u8; 16];
+            stream.read_exact(&mut ip).await?;
+            Ipv6Addr::from(ip).to_string()
+        }
+        _ => {
+            // 0x08：不支持的地址类型
+            socks5_reply(stream, 0x08).await?;
+            return Err(invalid("unsupported SOCKS address type"));
+        }
+    };
+    let port = stream.read_u16().await?;
+    Ok((host, port))
+}
+
+async fn socks5_reply<S: AsyncWrite + Unpin>(stream: &mut S, code: u8) -> io::Result<()> {
+    stream.write_all(&[5, code, 0, 1, 0, 0, 0, 0, 0, 0]).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_forward_spec() {
+        let local = ForwardSpec::parse(ForwardKind::Local, "8080:db.internal:5432").unwrap();
+        assert_eq!(local.bind_address, "127.0.0.1");
+        assert_eq!(local.bind_port, 8080);
+        assert_eq!(local.target_host.as_deref(), Some("db.internal"));
+        assert_eq!(local.target_port, Some(5432));
+
+        let remote =
+            ForwardSpec::parse(ForwardKind::Remote, "0.0.0.0:9000:localhost:3000").unwrap();
+        assert_eq!(remote.bind_address, "0.0.0.0");
+        assert_eq!(remote.target_port, Some(3000));
+
+        let ipv6 = ForwardSpec::parse(ForwardKind::Local, "[::1]:8080:[fd00::2]:80").unwrap();
+        assert_eq!(ipv6.bind_address, "::1");
+        assert_eq!(ipv6.target_host.as_deref(), Some("fd00::2"));
+
+        let socks = ForwardSpec::parse(ForwardKind::Dynamic, "1080").unwrap();
+        assert_eq!((socks.bind_port, socks.target_host), (1080, None));
+        let socks = ForwardSpec::parse(ForwardKind::Dynamic, "*:1080").unwrap();
+        assert_eq!(socks.bind_address, "*");
+
+        assert!(ForwardSpec::parse(ForwardKind::Local, "8080").is_err());
+        assert!(ForwardSpec::parse(ForwardKind::Local, "http:host:80").is_err());
+        assert!(ForwardSpec::parse(ForwardKind::Dynamic, "1:2:3").is_err());
+    }
+
+    #[test]
+    fn test_forward_spec_requires_target() {
+        let spec: ForwardSpec =
+            serde_json::from_str(r#"{"kind": "local", "bind_port": 8080}"#).unwrap();
+        assert!(spec.validate().is_err());
+        let spec: ForwardSpec =
+            serde_json::from_str(r#"{"kind": "dynamic", "bind_port": 0}"#).unwrap();
+        assert!(spec.validate().is_ok());
+    }
+
+    // 与 ssh.rs 中的测试相同，需要一个可访问的 sshd：
+    //   cargo test -- --ignored ssh
+    // 通过 -L 转发连接 sshd 自身的监听端口（docker-compose 中的 sshd 容器为 2222，
+    // 需要开启 AllowTcpForwarding），应读到 SSH 协议标识
+    #[tokio::test]
+    #[ignore]
+    async fn test_local_forward_against_local_sshd() {
+        use crate::ssh::{connect, HostKeyPolicy, SshConnectOptions};
+
+        let host = std::env::var("HUAAN_TEST_SSH_HOST").unwrap_or_else(|_| "127.0.0.1".into());
+        let port = std::env::var("HUAAN_TEST_SSH_PORT")
+            .ok()
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(2222);
+        let user = std::env::var("HUAAN_TEST_SSH_USER").unwrap_or_else(|_| "huaan".into());
+        let password = std::env::var("HUAAN_TEST_SSH_PASSWORD").unwrap_or_else(|_| "huaan".into());
+
+        let options = SshConnectOptions::new(host, port, user, Some(password), None, None);
+        let handle = connect(
+            0,
+            &options,
+            HostKeyPolicy::AcceptNew,
+            RemoteForwards::default(),
+        )
+        .await
+        .expect("ssh connect failed");
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let forward = tokio::spawn(async move {
+            let (socket, peer) = listener.accept().await.unwrap();
+            forward_connection(
+                &handle,
+                socket,
+                ("127.0.0.1".to_string(), port),
+                (peer.ip().to_string(), peer.port() as u32),
+            )
+            .await
+        });
+
+        let mut client = TcpStream::connect(address).await.unwrap();
+        let mut banner = [0u8; 7];
+        client.read_exact(&mut banner).await.unwrap();
+        assert_eq!(&banner, b"SSH-2.0");
+        drop(client);
+        let _ = forward.await;
+    }
+
+    #[tokio::test]
+    async fn test_socks5_request() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let request = tokio::spawn(async move { socks5_request(&mut server).await });
+
+        client.write_all(&[5, 1, 0]).await.unwrap();
+        let mut reply = [0u8; 2];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [5, 0]);
+
+        let mut connect = vec![5, 1, 0, 3, 11];
+        connect.extend_from_slice(b"example.com");
+        connect.extend_from_slice(&443u16.to_be_bytes());
+        client.write_all(&connect).await.unwrap();
+        let target = request.await.unwrap().unwrap();
+        assert_eq!(target, ("example.com".to_string(), 443));
+    }
+
+    #[tokio::test]
+    async fn test_socks5_rejects_auth_only_clients() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let request = tokio::spawn(async move { socks5_request(&mut server).await });
+
+        // 只提供用户名密码认证（0x02）
+        client.write_all(&[5, 1, 2]).await.unwrap();
+        let mut reply = [0u8; 2];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [5, 0xff]);
+        assert!(request.await.unwrap().is_err());
+    }
+}
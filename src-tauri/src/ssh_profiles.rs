@@ -0,0 +1,468 @@
+use crate::ssh::{HostKeyChecking, SshKeepalive, SshReconnect};
+use crate::ssh_forward::{ForwardKind, ForwardSpec};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+const PROFILES_FILE: &str = "ssh_profiles.json";
+
+/// 已保存的 SSH 连接配置（不包含密码等机密信息）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SshProfile {
+    /// 唯一名称
+    pub name: String,
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub user: Option<String>,
+    /// 密码在密钥存储中的引用（`secret:<id>`），不保存明文
+    #[serde(default)]
+    pub password_ref: Option<String>,
+    /// 私钥路径（支持 ~）
+    #[serde(default)]
+    pub identity_file: Option<String>,
+    /// 跳板机，格式为 `[user@]host[:port]`，多个跳板机用逗号分隔（同 ProxyJump）
+    #[serde(default)]
+    pub jump_host: Option<String>,
+    /// 连接后设置的远程环境变量
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// shell 启动后自动执行的命令
+    #[serde(default)]
+    pub startup_command: Option<String>,
+    /// 连接后建立的端口转发
+    #[serde(default)]
+    pub forwards: Vec<ForwardSpec>,
+    #[serde(default)]
+    pub keepalive: SshKeepalive,
+    /// 断线重连设置，包括重连后要重新挂载的 tmux 会话
+    #[serde(default)]
+    pub reconnect: SshReconnect,
+    /// 未知主机密钥的处理方式（`ask` 或 `accept-new`）
+    #[serde(default)]
+    pub host_key_checking: HostKeyChecking,
+}
+
+fn default_port() -> u16 {
+    22
+}
+
+/// SSH 连接配置存储，保存在应用配置目录下的 ssh_profiles.json
+pub struct SshProfileStore {
+    path: PathBuf,
+}
+
+impl SshProfileStore {
+    pub fn open(app_handle: &AppHandle) -> Result<Self, String> {
+        let config_dir = app_handle
+            .path()
+            .app_config_dir()
+            .map_err(|e| format!("无法获取应用配置目录: {}", e))?;
+        Ok(Self::at(config_dir.join(PROFILES_FILE)))
+    }
+
+    pub fn at(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn list(&self) -> Result<Vec<SshProfile>, String> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let content =
+            fs::read_to_string(&self.path).map_err(|e| format!("无法读取 SSH 配置文件: {}", e))?;
+        serde_json::from_str(&content).map_err(|e| format!("无法解析 SSH 配置文件: {}", e))
+    }
+
+    pub fn get(&self, name: &str) -> Result<SshProfile, String> {
+        self.list()?
+            .into_iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| format!("SSH 配置 '{}' 不存在", name))
+    }
+
+    /// 新建或覆盖同名配置
+    pub fn save(&self, profile: SshProfile) -> Result<(), String> {
+        validate_profile(&profile)?;
+
+        let mut profiles = self.list()?;
+        match profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) => *existing = profile,
+            None => profiles.push(profile),
+        }
+        self.write(&profiles)
+    }
+
+    /// 重命名配置
+    pub fn rename(&self, old_name: &str, new_name: &str) -> Result<(), String> {
+        let mut profiles = self.list()?;
+        if profiles.iter().any(|p| p.name == new_name) {
+            return Err(format!("SSH 配置 '{}' 已存在", new_name));
+        }
+        let profile = profiles
+            .iter_mut()
+            .find(|p| p.name == old_name)
+            .ok_or_else(|| format!("SSH 配置 '{}' 不存在", old_name))?;
+        profile.name = new_name.to_string();
+        validate_profile(profile)?;
+        self.write(&profiles)
+    }
+
+    pub fn remove(&self, name: &str) -> Result<(), String> {
+        let mut profiles = self.list()?;
+        let before = profiles.len();
+        profiles.retain(|p| p.name != name);
+        if profiles.len() == before {
+            return Err(format!("SSH 配置 '{}' 不存在", name));
+        }
+        self.write(&profiles)
+    }
+
+    /// 从 OpenSSH 配置文件导入 Host 块，跳过已存在的同名配置
+    ///
+    /// 返回新导入的配置
+    pub fn import_ssh_config(&self, config_path: &Path) -> Result<Vec<SshProfile>, String> {
+        let content = fs::read_to_string(config_path)
+            .map_err(|e| format!("无法读取 {}: {}", config_path.display(), e))?;
+
+        let mut profiles = self.list()?;
+        let mut imported = Vec::new();
+        for profile in parse_ssh_config(&content) {
+            if profiles.iter().any(|p| p.name == profile.name) {
+                continue;
+            }
+            profiles.push(profile.clone());
+            imported.push(profile);
+        }
+
+        self.write(&profiles)?;
+        Ok(imported)
+    }
+
+    fn write(&self, profiles: &[SshProfile]) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("无法创建配置目录: {}", e))?;
+        }
+
+        let json = serde_json::to_string_pretty(profiles)
+            .map_err(|e| format!("无法序列化 SSH 配置: {}", e))?;
+        fs::write(&self.path, json).map_err(|e| format!("无法保存 SSH 配置文件: {}", e))
+    }
+}
+
+fn validate_profile(profile: &SshProfile) -> Result<(), String> {
+    if profile.name.trim().is_empty() {
+        return Err("SSH 配置名称不能为空".to_string());
+    }
+    if profile.host.trim().is_empty() {
+        return Err(format!("SSH 配置 '{}' 缺少主机地址", profile.name));
+    }
+    Ok(())
+}
+
+/// 默认的 OpenSSH 用户配置文件 ~/.ssh/config
+pub fn default_ssh_config_path() -> Result<PathBuf, String> {
+    let home = std::env::var("HOME")
+        .or_else(|_| std::env::var("USERPROFILE"))
+        .map_err(|e| format!("无法获取HOME目录: {}", e))?;
+    Ok(PathBuf::from(home).join(".ssh").join("config"))
+}
+
+/// 解析 OpenSSH 配置中的 Host 块
+///
+/// 每个不含通配符的别名生成一个配置；`Match` 块和通配符 Host 会被忽略
+fn parse_ssh_config(content: &str) -> Vec<SshProfile> {
+    let mut profiles: Vec<SshProfile> = Vec::new();
+    // 当前 Host 块对应的配置在 profiles 中的下标
+    let mut current: Vec<usize> = Vec::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = match line.split_once(|c: char| c.is_whitespace() || c == '=') {
+            Some((key, value)) => (
+                key.to_lowercase(),
+                value.trim_start_matches([' ', '\t', '=']).trim(),
+            ),
+            None => continue,
+        };
+
+        match key.as_str() {
+            "host" => {
+                current.clear();
+                for alias in value.split_whitespace() {
+                    if alias.contains(['*', '?', '!']) {
+                        continue;
+                    }
+                    profiles.push(SshProfile {
+                        name: alias.to_string(),
+                        host: alias.to_string(),
+                        port: default_port(),
+                        user: None,
+                        password_ref: None,
+                        identity_file: None,
+                        jump_host: None,
+                        env: HashMap::new(),
+                        startup_command: None,
+                        forwards: Vec::new(),
+                        keepalive: SshKeepalive::default(),
+                        reconnect: SshReconnect::default(),
+                        host_key_checking: HostKeyChecking::default(),
+                    });
+                    current.push(profiles.len() - 1);
+                }
+            }
+            "match" => current.clear(),
+            _ => {
+                for &index in &current {
+                    apply_ssh_config_option(&mut profiles[index], &key, value);
+                }
+            }
+        }
+    }
+
+    profiles
+}
+
+fn apply_ssh_config_option(profile: &mut SshProfile, key: &str, value: &str) {
+    let value = value.trim_matches('"');
+    match key {
+        "hostname" => profile.host = value.to_string(),
+        "port" => {
+            if let Ok(port) = value.parse() {
+                profile.port = port;
+            }
+        }
+        "user" => profile.user = Some(value.to_string()),
+        // 与 OpenSSH 一致，以第一次出现的 IdentityFile 为准
+        "identityfile" if profile.identity_file.is_none() => {
+            profile.identity_file = Some(value.to_string())
+        }
+        "proxyjump" if !value.eq_ignore_ascii_case("none") => {
+            profile.jump_host = Some(value.to_string())
+        }
+        // 配置文件中用空格分隔监听地址和目标，例如 `LocalForward 8080 localhost:80`
+        "localforward" | "remoteforward" | "dynamicforward" => {
+            let kind = match key {
+                "localforward" => ForwardKind::Local,
+                "remoteforward" => ForwardKind::Remote,
+                _ => ForwardKind::Dynamic,
+            };
+            let spec = value.split_whitespace().collect::<Vec<_>>().join(":");
+            if let Ok(forward) = ForwardSpec::parse(kind, &spec) {
+                profile.forwards.push(forward);
+            }
+        }
+        // 只支持 accept-new；no/off 会接受变更的密钥，不予采用
+        "stricthostkeychecking" if value.eq_ignore_ascii_case("accept-new") => {
+            profile.host_key_checking = HostKeyChecking::AcceptNew
+        }
+        "serveraliveinterval" => {
+            if let Ok(secs) = value.parse() {
+                profile.keepalive.interval_secs = secs;
+            }
+        }
+        "serveralivecountmax" => {
+            if let Ok(count) = value.parse() {
+                profile.keepalive.max_missed = count;
+            }
+        }
+        "setenv" => {
+            for pair in value.split_whitespace() {
+                if let Some((name, val)) = pair.split_once('=') {
+                    profile
+                        .env
+                        .insert(name.to_string(), val.trim_matches('"').to_string());
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_CONFIG: &str = r#"
+# 全局设置
+Host *
+    ServerAliveInterval 60
+
+Host prod prod-alias
+    HostName 10.0.0.5
+    Port 2200
+    User deploy
+    IdentityFile ~/.ssh/prod_ed25519
+    IdentityFile ~/.ssh/fallback
+    ProxyJump bastion
+    SetEnv APP_ENV=production LANG="C.UTF-8"
+    LocalForward 5433 db.internal:5432
+    DynamicForward 1080
+    ServerAliveInterval 30
+    ServerAliveCountMax 5
+
+Host=bastion
+    HostName=bastion.example.com
+    User ops
+    StrictHostKeyChecking accept-new
+
+Match host *.internal
+    User ignored
+"#;
+
+    fn temp_store(name: &str) -> SshProfileStore {
+        let path = std::env::temp_dir().join(format!(
+            "huaan_ssh_profiles_{}_{}.json",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        SshProfileStore::at(path)
+    }
+
+    #[test]
+    fn test_parse_ssh_config() {
+        let profiles = parse_ssh_config(SAMPLE_CONFIG);
+        let names: Vec<_> = profiles.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["prod", "prod-alias", "bastion"]);
+
+        let prod = &profiles[0];
+        assert_eq!(prod.host, "10.0.0.5");
+        assert_eq!(prod.port, 2200);
+        assert_eq!(prod.user.as_deref(), Some("deploy"));
+        assert_eq!(prod.identity_file.as_deref(), Some("~/.ssh/prod_ed25519"));
+        assert_eq!(prod.jump_host.as_deref(), Some("bastion"));
+        assert_eq!(
+            (prod.keepalive.interval_secs, prod.keepalive.max_missed),
+            (30, 5)
+        );
+        assert_eq!(
+            prod.env.get("APP_ENV").map(String::as_str),
+            Some("production")
+        );
+        assert_eq!(prod.env.get("LANG").map(String::as_str), Some("C.UTF-8"));
+        let forwards: Vec<_> = prod
+            .forwards
+            .iter()
+            .map(|f| (f.kind, f.bind_port, f.target_host.as_deref()))
+            .collect();
+        assert_eq!(
+            forwards,
+            vec![
+                (ForwardKind::Local, 5433, Some("db.internal")),
+                (ForwardKind::Dynamic, 1080, None),
+            ]
+        );
+        assert_eq!(profiles[1].host, "10.0.0.5");
+
+        let bastion = &profiles[2];
+        assert_eq!(bastion.host, "bastion.example.com");
+        assert_eq!(bastion.port, 22);
+        assert_eq!(bastion.user.as_deref(), Some("ops"));
+        assert_eq!(bastion.host_key_checking, HostKeyChecking::AcceptNew);
+        assert_eq!(prod.host_key_checking, HostKeyChecking::Ask);
+    }
+
+    #[test]
+    fn test_profile_crud() {
+        let store = temp_store("crud");
+        assert!(store.list().unwrap().is_empty());
+
+        let mut profile = parse_ssh_config(SAMPLE_CONFIG).remove(2);
+        store.save(profile.clone()).unwrap();
+        assert_eq!(store.get("bastion").unwrap(), profile);
+
+        profile.port = 2022;
+        store.save(profile.clone()).unwrap();
+        assert_eq!(store.list().unwrap().len(), 1);
+        assert_eq!(store.get("bastion").unwrap().port, 2022);
+
+        store.rename("bastion", "jump").unwrap();
+        assert!(store.get("bastion").is_err());
+        assert_eq!(store.get("jump").unwrap().host, "bastion.example.com");
+
+        store.remove("jump").unwrap();
+        assert!(store.remove("jump").is_err());
+        assert!(store.list().unwrap().is_empty());
+
+        profile.host = String::new();
+        assert!(store.save(profile).is_err());
+        let _ = fs::remove_file(&store.path);
+    }
+
+    #[test]
+    fn test_import_skips_existing() {
+        let store = temp_store("import");
+        let config_path =
+            std::env::temp_dir().join(format!("huaan_ssh_config_{}", std::process::id()));
+        fs::write(&config_path, SAMPLE_CONFIG).unwrap();
+
+        assert_eq!(store.import_ssh_config(&config_path).unwrap().len(), 3);
+        assert!(store.import_ssh_config(&config_path).unwrap().is_empty());
+        assert_eq!(store.list().unwrap().len(), 3);
+
+        let _ = fs::remove_file(&config_path);
+        let _ = fs::remove_file(&store.path);
+    }
+
+    #[test]
+    fn test_imported_proxy_jump_alias() {
+        use crate::ssh::SshConnectOptions;
+
+        let store = temp_store("proxy_jump");
+        let config_path =
+            std::env::temp_dir().join(format!("huaan_ssh_config_jump_{}", std::process::id()));
+        let config = format!(
+            "{}\nHost inner\n    HostName 10.0.0.9\n    User app\n    ProxyJump admin@prod:2222\n\nHost loop\n    HostName loop.example.com\n    User app\n    ProxyJump loop\n",
+            SAMPLE_CONFIG
+        );
+        fs::write(&config_path, config).unwrap();
+        store.import_ssh_config(&config_path).unwrap();
+        let profiles = store.list().unwrap();
+        let no_password = |_: &SshProfile| Ok(None);
+
+        // ProxyJump bastion 指向导入的 bastion 配置，而不是名为 bastion 的主机
+        let prod = store.get("prod").unwrap();
+        let options =
+            SshConnectOptions::from_profile(&prod, None, None, &profiles, &no_password).unwrap();
+        let hops: Vec<_> = options
+            .jump_hosts
+            .iter()
+            .map(|hop| (hop.host.as_str(), hop.port, hop.username.as_str()))
+            .collect();
+        assert_eq!(hops, vec![("bastion.example.com", 22, "ops")]);
+
+        // 显式的用户和端口优先，被引用配置自己的跳板机排在它之前
+        let inner = store.get("inner").unwrap();
+        let options =
+            SshConnectOptions::from_profile(&inner, None, None, &profiles, &no_password).unwrap();
+        let hops: Vec<_> = options
+            .jump_hosts
+            .iter()
+            .map(|hop| (hop.host.as_str(), hop.port, hop.username.as_str()))
+            .collect();
+        assert_eq!(
+            hops,
+            vec![
+                ("bastion.example.com", 22, "ops"),
+                ("10.0.0.5", 2222, "admin")
+            ]
+        );
+
+        let looped = store.get("loop").unwrap();
+        assert!(
+            SshConnectOptions::from_profile(&looped, None, None, &profiles, &no_password).is_err()
+        );
+
+        let _ = fs::remove_file(&config_path);
+        let _ = fs::remove_file(&store.path);
+    }
+}
@@ -1,13 +1,15 @@
+use crate::commands::executor::expand_tilde;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeSet, HashMap, VecDeque};
 use std::fmt;
+use std::path::PathBuf;
 use std::process::Stdio;
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter};
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command;
-use tokio::sync::{Mutex, Semaphore};
+use tokio::sync::{mpsc, Mutex, Semaphore};
 use tokio::task::JoinHandle;
 use tokio::time::{interval, Duration};
 use tracing::{debug, error, info, warn};
@@ -28,6 +30,40 @@ pub struct Task {
     pub end_time: Option<i64>,
     #[serde(default)]
     pub env_vars: HashMap<String, String>,
+    /// 必须先成功完成的任务 ID，run_all_tasks 按依赖关系调度
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// 工作目录（支持 ~），为空时使用应用的当前目录
+    #[serde(default)]
+    pub working_dir: Option<String>,
+    #[serde(default)]
+    pub shell: TaskShell,
+    /// 传给命令的参数：通过 shell 执行时为 $1、$2…，直接执行时为 argv
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// 执行任务命令的方式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskShell {
+    Sh,
+    Bash,
+    Zsh,
+    /// PowerShell，Windows 上使用系统自带的 powershell.exe
+    Pwsh,
+    /// 不经过 shell，command 为可执行文件，args 为参数
+    None,
+}
+
+impl Default for TaskShell {
+    fn default() -> Self {
+        if cfg!(target_os = "windows") {
+            TaskShell::Pwsh
+        } else {
+            TaskShell::Sh
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -38,6 +74,8 @@ pub enum TaskStatus {
     Success,
     Failed,
     Cancelled,
+    /// 依赖的任务失败、被取消或被跳过，未执行
+    Skipped,
 }
 
 struct TaskOutput {
@@ -59,6 +97,163 @@ impl TaskOutput {
     }
 }
 
+// 任务结束时通知 DAG 调度器；任务被取消（abort）时随 future 一起释放，按失败处理
+struct FinishNotice {
+    task_id: String,
+    success: bool,
+    tx: mpsc::UnboundedSender<(String, bool)>,
+}
+
+impl Drop for FinishNotice {
+    fn drop(&mut self) {
+        let _ = self.tx.send((self.task_id.clone(), self.success));
+    }
+}
+
+// 按依赖关系调度的一次 run_all_tasks：依赖全部成功的任务可以运行，
+// 有依赖失败的任务被跳过
+struct Schedule {
+    deps: HashMap<String, Vec<String>>,
+    pending: BTreeSet<String>,
+    results: HashMap<String, bool>,
+}
+
+impl Schedule {
+    fn new(deps: HashMap<String, Vec<String>>) -> Self {
+        Self {
+            pending: deps.keys().cloned().collect(),
+            deps,
+            results: HashMap::new(),
+        }
+    }
+
+    // 取出所有可以运行的任务和需要跳过的任务（附带原因），跳过会继续传递给下游
+    fn advance(&mut self) -> (Vec<String>, Vec<(String, String)>) {
+        let mut ready = Vec::new();
+        let mut skipped = Vec::new();
+        loop {
+            let mut changed = false;
+            for id in self.pending.clone() {
+                let mut blocked = false;
+                let mut failed = None;
+                for dep in &self.deps[&id] {
+                    match self.results.get(dep) {
+                        Some(true) => {}
+                        Some(false) => failed = Some(dep.clone()),
+                        None if self.deps.contains_key(dep) => blocked = true,
+                        None => failed = Some(dep.clone()),
+                    }
+                }
+                if let Some(dep) = failed {
+                    self.pending.remove(&id);
+                    self.results.insert(id.clone(), false);
+                    skipped.push((id, dep));
+                    changed = true;
+                } else if !blocked {
+                    self.pending.remove(&id);
+                    ready.push(id);
+                }
+            }
+            if !changed {
+                return (ready, skipped);
+            }
+        }
+    }
+
+    fn finish(&mut self, task_id: String, success: bool) {
+        self.results.insert(task_id, success);
+    }
+}
+
+// 按任务的 shell、参数和工作目录构造进程
+fn build_command(task: &Task) -> Result<Command> {
+    let mut cmd = match task.shell {
+        TaskShell::None => {
+            if task.command.trim().is_empty() {
+                return Err(anyhow::anyhow!("Task {} has no command", task.id));
+            }
+            let mut cmd = Command::new(task.command.trim());
+            cmd.args(&task.args);
+            cmd
+        }
+        // PowerShell 没有位置参数，参数用单引号转义后拼接到命令之后
+        TaskShell::Pwsh => {
+            let program = if cfg!(target_os = "windows") {
+                "powershell.exe"
+            } else {
+                "pwsh"
+            };
+            let mut script = task.command.clone();
+            for arg in &task.args {
+                script.push_str(&format!(" '{}'", arg.replace('\'', "''")));
+            }
+            let mut cmd = Command::new(program);
+            cmd.args(["-NoProfile", "-Command", &script]);
+            cmd
+        }
+        // `sh -c command name args...`：任务名作为 $0，参数依次为 $1、$2…
+        shell => {
+            let program = match shell {
+                TaskShell::Bash => "bash",
+                TaskShell::Zsh => "zsh",
+                _ => "sh",
+            };
+            let mut cmd = Command::new(program);
+            cmd.arg("-c")
+                .arg(&task.command)
+                .arg(&task.name)
+                .args(&task.args);
+            cmd
+        }
+    };
+
+    if let Some(dir) = &task.working_dir {
+        let dir = PathBuf::from(expand_tilde(dir.trim()).map_err(anyhow::Error::msg)?);
+        if !dir.is_dir() {
+            return Err(anyhow::anyhow!(
+                "Working directory does not exist: {}",
+                dir.display()
+            ));
+        }
+        cmd.current_dir(dir);
+    }
+    Ok(cmd)
+}
+
+/// 查找依赖环，返回环上的任务（首尾相同），例如 `[a, b, a]`
+fn find_cycle(deps: &HashMap<String, Vec<String>>) -> Option<Vec<String>> {
+    fn visit(
+        id: &str,
+        deps: &HashMap<String, Vec<String>>,
+        path: &mut Vec<String>,
+        done: &mut BTreeSet<String>,
+    ) -> Option<Vec<String>> {
+        if let Some(start) = path.iter().position(|p| p == id) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(id.to_string());
+            return Some(cycle);
+        }
+        if done.contains(id) {
+            return None;
+        }
+        path.push(id.to_string());
+        for dep in deps.get(id).into_iter().flatten() {
+            if let Some(cycle) = visit(dep, deps, path, done) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        done.insert(id.to_string());
+        None
+    }
+
+    let mut done = BTreeSet::new();
+    let mut ids: Vec<_> = deps.keys().collect();
+    ids.sort();
+    ids.into_iter()
+        .find_map(|id| visit(id, deps, &mut Vec::new(), &mut done))
+}
+
 impl fmt::Display for TaskOutput {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let output = self
@@ -90,15 +285,48 @@ impl TaskManager {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_task(
         &self,
         id: String,
         name: String,
         command: String,
         env_vars: Option<HashMap<String, String>>,
+        depends_on: Option<Vec<String>>,
+        working_dir: Option<String>,
+        shell: Option<TaskShell>,
+        args: Option<Vec<String>>,
     ) -> Result<Task> {
         info!("Creating task: {} ({})", name, id);
 
+        let mut seen = BTreeSet::new();
+        let mut depends_on = depends_on.unwrap_or_default();
+        depends_on.retain(|dep| seen.insert(dep.clone()));
+        let mut tasks = self.tasks.lock().await;
+
+        // 依赖必须是已创建的任务，且加入后不能形成环
+        if let Some(dep) = depends_on
+            .iter()
+            .find(|dep| **dep != id && !tasks.contains_key(*dep))
+        {
+            return Err(anyhow::anyhow!(
+                "Task {} depends on unknown task {}",
+                id,
+                dep
+            ));
+        }
+        let mut graph: HashMap<String, Vec<String>> = tasks
+            .values()
+            .map(|task| (task.id.clone(), task.depends_on.clone()))
+            .collect();
+        graph.insert(id.clone(), depends_on.clone());
+        if let Some(cycle) = find_cycle(&graph) {
+            return Err(anyhow::anyhow!(
+                "Task dependencies form a cycle: {}",
+                cycle.join(" -> ")
+            ));
+        }
+
         let task = Task {
             id: id.clone(),
             name,
@@ -109,14 +337,27 @@ impl TaskManager {
             start_time: None,
             end_time: None,
             env_vars: env_vars.unwrap_or_default(),
+            depends_on,
+            working_dir: working_dir.filter(|dir| !dir.trim().is_empty()),
+            shell: shell.unwrap_or_default(),
+            args: args.unwrap_or_default(),
         };
 
-        let mut tasks = self.tasks.lock().await;
         tasks.insert(id, task.clone());
         Ok(task)
     }
 
     pub async fn run_task(&self, task_id: String, app_handle: AppHandle) -> Result<()> {
+        self.start_task(task_id, app_handle, None).await
+    }
+
+    // 启动任务；传入 notify 时任务结束后发送 (task_id, 是否成功)
+    async fn start_task(
+        &self,
+        task_id: String,
+        app_handle: AppHandle,
+        notify: Option<mpsc::UnboundedSender<(String, bool)>>,
+    ) -> Result<()> {
         info!("Starting task: {}", task_id);
 
         let task = {
@@ -145,26 +386,20 @@ impl TaskManager {
         let outputs = Arc::clone(&self.outputs);
         let errors = Arc::clone(&self.errors);
         let task_id_clone = task_id.clone();
-        let command = task.command.clone();
         let semaphore = Arc::clone(&self.semaphore);
 
-        let env_vars = task.env_vars.clone();
-
         let handle = tokio::spawn(async move {
+            let mut notice = notify.map(|tx| FinishNotice {
+                task_id: task_id_clone.clone(),
+                success: false,
+                tx,
+            });
+
             // 获取信号量许可
             let _permit = semaphore.acquire().await.unwrap();
             debug!("Acquired semaphore for task: {}", task_id_clone);
 
-            let result = Self::execute_command(
-                &command,
-                &task_id_clone,
-                &app_handle,
-                &tasks,
-                &outputs,
-                &errors,
-                &env_vars,
-            )
-            .await;
+            let result = Self::execute_command(&task, &app_handle, &tasks, &outputs, &errors).await;
 
             let mut tasks_lock = tasks.lock().await;
             if let Some(task) = tasks_lock.get_mut(&task_id_clone) {
@@ -184,7 +419,12 @@ impl TaskManager {
                 drop(errors_lock);
 
                 match result {
-                    Ok(_) => task.status = TaskStatus::Success,
+                    Ok(_) => {
+                        task.status = TaskStatus::Success;
+                        if let Some(notice) = notice.as_mut() {
+                            notice.success = true;
+                        }
+                    }
                     Err(e) => {
                         task.status = TaskStatus::Failed;
                         task.error.push_str(&format!("\nError: {}", e));
@@ -201,36 +441,20 @@ impl TaskManager {
     }
 
     async fn execute_command(
-        command: &str,
-        task_id: &str,
+        task: &Task,
         app_handle: &AppHandle,
         _tasks: &Arc<Mutex<HashMap<String, Task>>>,
         outputs: &Arc<Mutex<HashMap<String, TaskOutput>>>,
         errors: &Arc<Mutex<HashMap<String, TaskOutput>>>,
-        env_vars: &HashMap<String, String>,
     ) -> Result<()> {
-        info!("Executing command for task {}: {}", task_id, command);
-
-        let shell = if cfg!(target_os = "windows") {
-            "powershell.exe"
-        } else {
-            "sh"
-        };
+        let task_id = task.id.as_str();
+        info!("Executing command for task {}: {}", task_id, task.command);
 
-        let shell_arg = if cfg!(target_os = "windows") {
-            "-Command"
-        } else {
-            "-c"
-        };
-
-        let mut cmd = Command::new(shell);
-        cmd.arg(shell_arg)
-            .arg(command)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+        let mut cmd = build_command(task)?;
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
 
         // 应用环境变量
-        for (key, value) in env_vars {
+        for (key, value) in &task.env_vars {
             debug!("Setting env var for task {}: {}={}", task_id, key, value);
             cmd.env(key, value);
         }
@@ -345,27 +569,96 @@ impl TaskManager {
         Ok(())
     }
 
+    /// 按依赖关系运行全部任务，互不依赖的任务并行执行（受 MAX_CONCURRENT_TASKS 限制）
+    ///
+    /// 调度在后台进行，本方法在所有任务重置为 pending 后立即返回
     pub async fn run_all_tasks(&self, app_handle: AppHandle) -> Result<()> {
-        let task_ids: Vec<String> = {
-            let tasks = self.tasks.lock().await;
-            tasks.keys().cloned().collect()
+        let deps: HashMap<String, Vec<String>> = {
+            let mut tasks = self.tasks.lock().await;
+            let deps = tasks
+                .values()
+                .map(|task| (task.id.clone(), task.depends_on.clone()))
+                .collect();
+            if let Some(cycle) = find_cycle(&deps) {
+                return Err(anyhow::anyhow!(
+                    "Task dependencies form a cycle: {}",
+                    cycle.join(" -> ")
+                ));
+            }
+            for task in tasks.values_mut() {
+                task.status = TaskStatus::Pending;
+                task.start_time = None;
+                task.end_time = None;
+                let _ = app_handle.emit("task-updated", task.clone());
+            }
+            deps
         };
 
-        let mut handles = vec![];
-        for task_id in task_ids {
-            let self_clone = Arc::new(self.clone());
-            let app_handle_clone = app_handle.clone();
-            let handle = tokio::spawn(async move {
-                let _ = self_clone.run_task(task_id, app_handle_clone).await;
-            });
-            handles.push(handle);
-        }
+        let manager = self.clone();
+        tokio::spawn(async move {
+            manager.run_schedule(Schedule::new(deps), app_handle).await;
+        });
+        Ok(())
+    }
 
-        for handle in handles {
-            let _ = handle.await;
+    async fn run_schedule(&self, mut schedule: Schedule, app_handle: AppHandle) {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut running = 0;
+
+        loop {
+            let (ready, skipped) = schedule.advance();
+            let progressed = !ready.is_empty() || !skipped.is_empty();
+            for (task_id, dep) in skipped {
+                self.skip_task(&task_id, &dep, &app_handle).await;
+            }
+            for task_id in ready {
+                // 等待期间被取消的任务不再启动，按失败处理
+                let cancelled = self
+                    .get_task(&task_id)
+                    .await
+                    .is_none_or(|task| task.status == TaskStatus::Cancelled);
+                let started = !cancelled
+                    && self
+                        .start_task(task_id.clone(), app_handle.clone(), Some(tx.clone()))
+                        .await
+                        .is_ok();
+                if started {
+                    running += 1;
+                } else {
+                    schedule.finish(task_id, false);
+                }
+            }
+
+            // 没有运行中的任务且无法继续推进时，所有任务都已经结束或被跳过
+            if running == 0 {
+                if progressed && !schedule.pending.is_empty() {
+                    continue;
+                }
+                break;
+            }
+            match rx.recv().await {
+                Some((task_id, success)) => {
+                    running -= 1;
+                    schedule.finish(task_id, success);
+                }
+                None => break,
+            }
         }
+        info!("All scheduled tasks finished");
+    }
 
-        Ok(())
+    async fn skip_task(&self, task_id: &str, dep: &str, app_handle: &AppHandle) {
+        warn!(
+            "Skipping task {}: dependency {} did not succeed",
+            task_id, dep
+        );
+        let mut tasks = self.tasks.lock().await;
+        if let Some(task) = tasks.get_mut(task_id) {
+            task.status = TaskStatus::Skipped;
+            task.error = format!("Skipped: dependency {} did not succeed", dep);
+            task.end_time = Some(chrono::Utc::now().timestamp());
+            let _ = app_handle.emit("task-updated", task.clone());
+        }
     }
 
     pub async fn get_task(&self, task_id: &str) -> Option<Task> {
@@ -415,3 +708,145 @@ impl TaskManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+        edges
+            .iter()
+            .map(|(id, deps)| {
+                (
+                    id.to_string(),
+                    deps.iter().map(|dep| dep.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    fn task(command: &str, shell: TaskShell, args: &[&str]) -> Task {
+        Task {
+            id: "t1".to_string(),
+            name: "build".to_string(),
+            command: command.to_string(),
+            status: TaskStatus::Pending,
+            output: String::new(),
+            error: String::new(),
+            start_time: None,
+            end_time: None,
+            env_vars: HashMap::new(),
+            depends_on: Vec::new(),
+            working_dir: None,
+            shell,
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    fn argv(cmd: &Command) -> Vec<String> {
+        let cmd = cmd.as_std();
+        std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_build_command() {
+        let cmd = build_command(&task("make \"$1\"", TaskShell::Bash, &["all"])).unwrap();
+        assert_eq!(
+            argv(&cmd),
+            vec!["bash", "-c", "make \"$1\"", "build", "all"]
+        );
+
+        let cmd = build_command(&task("cargo", TaskShell::None, &["test", "--all"])).unwrap();
+        assert_eq!(argv(&cmd), vec!["cargo", "test", "--all"]);
+        assert!(build_command(&task(" ", TaskShell::None, &[])).is_err());
+
+        let cmd = build_command(&task("Write-Output", TaskShell::Pwsh, &["it's"])).unwrap();
+        assert_eq!(
+            argv(&cmd)[1..],
+            ["-NoProfile", "-Command", "Write-Output 'it''s'"]
+        );
+    }
+
+    #[test]
+    fn test_build_command_working_dir() {
+        let mut t = task("ls", TaskShell::Sh, &[]);
+        t.working_dir = Some(std::env::temp_dir().to_string_lossy().to_string());
+        let cmd = build_command(&t).unwrap();
+        assert_eq!(
+            cmd.as_std().get_current_dir(),
+            Some(std::env::temp_dir().as_path())
+        );
+
+        t.working_dir = Some("/nonexistent/huaan-task-dir".to_string());
+        assert!(build_command(&t).is_err());
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        let deps = graph(&[("build", &[]), ("test", &["build"]), ("deploy", &["test"])]);
+        assert!(find_cycle(&deps).is_none());
+
+        let deps = graph(&[("a", &["c"]), ("b", &["a"]), ("c", &["b"])]);
+        assert_eq!(find_cycle(&deps).unwrap(), vec!["a", "c", "b", "a"]);
+
+        let deps = graph(&[("a", &["a"])]);
+        assert_eq!(find_cycle(&deps).unwrap(), vec!["a", "a"]);
+    }
+
+    #[test]
+    fn test_schedule_runs_in_dependency_order() {
+        // build -> (unit, lint) -> deploy，docs 独立
+        let mut schedule = Schedule::new(graph(&[
+            ("build", &[]),
+            ("unit", &["build"]),
+            ("lint", &["build"]),
+            ("deploy", &["unit", "lint"]),
+            ("docs", &[]),
+        ]));
+
+        let (mut ready, skipped) = schedule.advance();
+        ready.sort();
+        assert_eq!(ready, vec!["build", "docs"]);
+        assert!(skipped.is_empty());
+        assert!(schedule.advance().0.is_empty());
+
+        schedule.finish("build".to_string(), true);
+        let (mut ready, _) = schedule.advance();
+        ready.sort();
+        assert_eq!(ready, vec!["lint", "unit"]);
+
+        schedule.finish("unit".to_string(), true);
+        assert!(schedule.advance().0.is_empty());
+        schedule.finish("lint".to_string(), true);
+        assert_eq!(schedule.advance().0, vec!["deploy"]);
+        assert!(schedule.pending.is_empty());
+    }
+
+    #[test]
+    fn test_schedule_skips_dependents_of_failed_task() {
+        let mut schedule = Schedule::new(graph(&[
+            ("build", &[]),
+            ("test", &["build"]),
+            ("deploy", &["test"]),
+            ("notify", &["deploy"]),
+        ]));
+        assert_eq!(schedule.advance().0, vec!["build"]);
+
+        schedule.finish("build".to_string(), false);
+        let (ready, mut skipped) = schedule.advance();
+        skipped.sort();
+        assert!(ready.is_empty());
+        assert_eq!(
+            skipped,
+            vec![
+                ("deploy".to_string(), "test".to_string()),
+                ("notify".to_string(), "deploy".to_string()),
+                ("test".to_string(), "build".to_string()),
+            ]
+        );
+        assert!(schedule.pending.is_empty());
+    }
+}
@@ -1,27 +1,535 @@
+use crate::broadcast::{BroadcastGroup, BroadcastGroups, BroadcastWrite};
+use crate::commands::executor::expand_tilde;
+use crate::process_info::{self, ForegroundJob};
+use crate::recording::{self, AsciicastRecorder};
+use crate::scrollback::{Scrollback, ScrollbackChunk, DEFAULT_SCROLLBACK_BYTES};
+use crate::search::{SearchMatch, SearchQuery, MAX_SEARCH_MATCHES};
+use crate::shell_integration::{osc_sequences, CommandRecord, CommandTracker, SemanticMark};
+use crate::ssh::{self, HostKeyPromptKind, HostKeyPrompts, SshChannelHandle, SshConnectOptions};
+use crate::ssh_forward::{ForwardSpec, ForwardStatus, SshForwards};
 use anyhow::Result;
-use portable_pty::{native_pty_system, CommandBuilder, PtyPair, PtySize};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use portable_pty::{native_pty_system, Child, CommandBuilder, ExitStatus, PtyPair, PtySize};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::io::{Read, Write};
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, Emitter};
 
+// close_terminal 发送 SIGHUP 后等待 shell 退出的时间，超时则发送 SIGKILL
+const CLOSE_GRACE_PERIOD: Duration = Duration::from_secs(3);
+
+// 输出合并：与 task.rs 相同的思路，按时间或大小把多次读取合并为一帧再推送
+pub const OUTPUT_BUFFER_MS: u64 = 16;
+const OUTPUT_FRAME_MAX_BYTES: usize = 64 * 1024;
+
+// 流量控制：已推送但前端未确认的数据超过上限时暂停读取 PTY
+const MAX_UNACKED_BYTES: u64 = 1024 * 1024;
+// 等待 shell 就绪（第一次上报 OSC 7）的最长时间，超时后仍然按就绪处理
+const READY_TIMEOUT: Duration = Duration::from_secs(5);
+// 前端长时间不确认时（例如页面已关闭）放弃流量控制，避免 shell 一直被阻塞
+const ACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+// start_terminal 的启动参数，所有字段都可以省略
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TerminalOptions {
+    // 初始工作目录，支持 ~，默认为 $HOME
+    pub cwd: Option<String>,
+    // 额外设置的环境变量，会覆盖继承的同名变量
+    pub env: HashMap<String, String>,
+    // 不传给 shell 的环境变量
+    pub env_remove: Vec<String>,
+    // shell 启动后执行的命令，可以是多行脚本
+    pub init_command: Option<String>,
+    // 以登录 shell 启动（读取 .profile / .zprofile 等），默认只是交互式 shell
+    pub login: bool,
+    pub rows: Option<u16>,
+    pub cols: Option<u16>,
+}
+
+impl TerminalOptions {
+    // 解析初始工作目录，目录不存在时报错
+    fn working_dir(&self) -> Result<PathBuf> {
+        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+        let dir = match self.cwd.as_deref() {
+            Some(cwd) if !cwd.trim().is_empty() => {
+                PathBuf::from(expand_tilde(cwd.trim()).map_err(anyhow::Error::msg)?)
+            }
+            _ => return Ok(PathBuf::from(home)),
+        };
+        if !dir.is_dir() {
+            return Err(anyhow::anyhow!(
+                "Working directory does not exist: {}",
+                dir.display()
+            ));
+        }
+        Ok(dir)
+    }
+
+    fn size(&self) -> (u16, u16) {
+        (
+            self.cols.filter(|&cols| cols > 0).unwrap_or(80),
+            self.rows.filter(|&rows| rows > 0).unwrap_or(24),
+        )
+    }
+}
+
+// 会话的底层连接：本地 PTY（持有 shell 子进程）或原生 SSH 通道
+pub enum SessionBackend {
+    Pty {
+        pair: PtyPair,
+        child: Box<dyn Child + Send + Sync>,
+    },
+    Ssh(SshChannelHandle),
+}
+
 pub struct TerminalSession {
     #[allow(dead_code)]
     pub id: u64,
-    pub pair: PtyPair,
+    pub backend: SessionBackend,
     pub writer: Box<dyn Write + Send>,
     pub current_dir: Arc<Mutex<PathBuf>>,
+    pub scrollback: Arc<Mutex<Scrollback>>,
+    pub output: OutputSink,
+    // 本地 shell 路径，SSH 会话为 user@host:port
+    pub shell: String,
+    pub pid: Option<u32>,
+    pub started_at: String,
+    // 当前附加到会话的前端数量，为 0 时会话在后台继续运行
+    pub attached: usize,
+}
+
+// list_terminals 返回的会话信息
+#[derive(Debug, Clone, Serialize)]
+pub struct TerminalInfo {
+    pub id: u64,
+    pub kind: &'static str,
+    pub shell: String,
+    pub cwd: String,
+    pub pid: Option<u32>,
+    pub started_at: String,
+    pub attached: bool,
+}
+
+// terminal-output-{id} 事件的内容，offset 用于断线重连后通过 scrollback 续传
+#[derive(Debug, Clone, Serialize)]
+pub struct TerminalOutput {
+    pub data: String,
+    // data 第一个字节的偏移量
+    pub offset: u64,
+    // 下一个输出字节的偏移量
+    pub end_offset: u64,
+}
+
+// terminal-exit-{id} 事件的内容
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TerminalExit {
+    pub code: Option<u32>,
+    // 被信号终止时的信号描述
+    pub signal: Option<String>,
+}
+
+impl From<&ExitStatus> for TerminalExit {
+    fn from(status: &ExitStatus) -> Self {
+        // portable-pty 只能通过 Display 获取信号名称
+        let signal = status
+            .to_string()
+            .strip_prefix("Terminated by ")
+            .map(str::to_string);
+        Self {
+            code: signal.is_none().then(|| status.exit_code()),
+            signal,
+        }
+    }
+}
+
+// terminal-ready-{id} 事件
+#[derive(Debug, Clone, Serialize)]
+pub struct TerminalReady {
+    pub cwd: String,
+    // 在 READY_TIMEOUT 内没有收到就绪信号
+    pub timed_out: bool,
+}
+
+// 等待就绪信号的一方；needs_osc7 为 false 时收到任何输出即视为就绪
+struct ReadyWaiter {
+    notify: mpsc::Sender<()>,
+    needs_osc7: bool,
+}
+
+// 命令记录及其输出文本
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandWithOutput {
+    #[serde(flatten)]
+    pub record: CommandRecord,
+    pub output: String,
+}
+
+// 终端屏幕快照，由 get_terminal_screen 返回
+#[derive(Debug, Clone, Serialize)]
+pub struct TerminalScreen {
+    pub rows: u16,
+    pub cols: u16,
+    // 每一行的可见文本，去掉行尾空白
+    pub lines: Vec<String>,
+    pub cursor_row: u16,
+    pub cursor_col: u16,
+    pub cursor_visible: bool,
+    // vim、htop 等全屏程序会切换到备用屏幕
+    pub alternate_screen: bool,
+    // OSC 0/2 设置的窗口标题
+    pub title: String,
+}
+
+impl From<&vt100::Screen> for TerminalScreen {
+    fn from(screen: &vt100::Screen) -> Self {
+        let (rows, cols) = screen.size();
+        let (cursor_row, cursor_col) = screen.cursor_position();
+        Self {
+            rows,
+            cols,
+            lines: screen
+                .rows(0, cols)
+                .map(|line| line.trim_end().to_string())
+                .collect(),
+            cursor_row,
+            cursor_col,
+            cursor_visible: !screen.hide_cursor(),
+            alternate_screen: screen.alternate_screen(),
+            title: screen.title().to_string(),
+        }
+    }
+}
+
+// 等待推送的输出帧
+#[derive(Default)]
+struct OutputFrame {
+    data: Vec<u8>,
+    offset: u64,
+}
+
+// 基于前端确认偏移量的流量控制
+struct FlowControl {
+    state: Mutex<FlowState>,
+    changed: Condvar,
+}
+
+struct FlowState {
+    // 已推送到前端的偏移量
+    sent: u64,
+    // 前端确认已处理的偏移量；为空表示前端没有确认，不做限制
+    acked: Option<u64>,
+    last_ack: Instant,
+    closed: bool,
+}
+
+impl FlowControl {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(FlowState {
+                sent: 0,
+                acked: None,
+                last_ack: Instant::now(),
+                closed: false,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    // 读取 PTY 前调用：未确认的数据过多时阻塞，直到前端确认、断开或超时
+    fn wait_for_window(&self) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let acked = match state.acked {
+                Some(acked) if !state.closed => acked,
+                _ => return,
+            };
+            if state.sent.saturating_sub(acked) <= MAX_UNACKED_BYTES {
+                return;
+            }
+            let waited = state.last_ack.elapsed();
+            if waited >= ACK_TIMEOUT {
+                eprintln!("Frontend stopped acknowledging output, disabling flow control");
+                state.acked = None;
+                return;
+            }
+            state = self
+                .changed
+                .wait_timeout(state, ACK_TIMEOUT - waited)
+                .unwrap()
+                .0;
+        }
+    }
+
+    fn sent(&self, offset: u64) {
+        self.state.lock().unwrap().sent = offset;
+    }
+
+    fn ack(&self, offset: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.acked = Some(state.acked.map_or(offset, |acked| acked.max(offset)));
+        state.last_ack = Instant::now();
+        self.changed.notify_all();
+    }
+
+    // 附加或断开前端时重置，等待新的前端确认
+    fn reset(&self) {
+        self.state.lock().unwrap().acked = None;
+        self.changed.notify_all();
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.changed.notify_all();
+    }
+}
+
+// 会话输出的出口：先写入 scrollback，再按帧推送 terminal-output-{id} 事件，
+// 同时处理 shell 集成的 OSC 7（当前目录）和 OSC 133（命令边界）
+#[derive(Clone)]
+pub struct OutputSink {
+    session_id: u64,
+    scrollback: Arc<Mutex<Scrollback>>,
+    decoder: Arc<Mutex<OutputDecoder>>,
+    frame: Arc<Mutex<OutputFrame>>,
+    flow: Arc<FlowControl>,
+    // 程序是否通过 DECSET 2004 开启了 bracketed paste 模式
+    bracketed_paste: Arc<AtomicBool>,
+    // 正在进行的 asciicast 录制
+    recorder: Arc<Mutex<Option<AsciicastRecorder>>>,
+    // 无界面的 VT 解析器，维护当前屏幕内容
+    screen: Arc<Mutex<vt100::Parser>>,
+    ready: Arc<Mutex<Option<ReadyWaiter>>>,
+    current_dir: Arc<Mutex<PathBuf>>,
+    commands: Arc<Mutex<CommandTracker>>,
+    app_handle: AppHandle,
+}
+
+impl OutputSink {
+    pub fn new(
+        session_id: u64,
+        scrollback: Arc<Mutex<Scrollback>>,
+        current_dir: Arc<Mutex<PathBuf>>,
+        (cols, rows): (u16, u16),
+        app_handle: AppHandle,
+    ) -> Self {
+        Self {
+            session_id,
+            scrollback,
+            decoder: Arc::new(Mutex::new(OutputDecoder::new())),
+            frame: Arc::new(Mutex::new(OutputFrame::default())),
+            flow: Arc::new(FlowControl::new()),
+            bracketed_paste: Arc::new(AtomicBool::new(false)),
+            recorder: Arc::new(Mutex::new(None)),
+            // 历史输出由 scrollback 保存，屏幕模型不需要回滚行
+            screen: Arc::new(Mutex::new(vt100::Parser::new(rows, cols, 0))),
+            ready: Arc::new(Mutex::new(None)),
+            current_dir,
+            commands: Arc::new(Mutex::new(CommandTracker::new(session_id))),
+            app_handle,
+        }
+    }
+
+    // 写入一次读取到的原始字节，数据先进入当前帧，由 flush 推送
+    // 不完整的 UTF-8 字符和转义序列留到下一次读取再处理
+    pub fn push(&self, bytes: &[u8]) -> Result<()> {
+        let ready = self.decoder.lock().unwrap().feed(bytes);
+        if ready.is_empty() {
+            return Ok(());
+        }
+
+        let offset = self.scrollback.lock().unwrap().push(&ready);
+        self.notify_ready(false);
+
+        // 解码器保证 ready 中的转义序列都是完整的
+        if ready.windows(2).any(|w| w == b"\x1b]") {
+            self.handle_osc(&String::from_utf8_lossy(&ready), offset);
+        }
+        if let Some(enabled) = bracketed_paste_change(&ready) {
+            self.bracketed_paste.store(enabled, Ordering::Relaxed);
+        }
+        self.record(|recorder| recorder.output(&String::from_utf8_lossy(&ready)));
+        self.screen.lock().unwrap().process(&ready);
+
+        let frame_len = {
+            let mut frame = self.frame.lock().unwrap();
+            if frame.data.is_empty() {
+                frame.offset = offset;
+            }
+            frame.data.extend_from_slice(&ready);
+            frame.data.len()
+        };
+        if frame_len >= OUTPUT_FRAME_MAX_BYTES {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    // 终端大小（列, 行）
+    pub fn size(&self) -> (u16, u16) {
+        let (rows, cols) = self.screen.lock().unwrap().screen().size();
+        (cols, rows)
+    }
+
+    // 同步屏幕模型的大小，并在录制中记录 resize 事件
+    pub fn resize(&self, cols: u16, rows: u16) {
+        self.screen.lock().unwrap().set_size(rows, cols);
+        self.record(|recorder| recorder.resize(cols, rows));
+    }
+
+    // 当前屏幕内容的快照
+    pub fn screen(&self) -> TerminalScreen {
+        TerminalScreen::from(self.screen.lock().unwrap().screen())
+    }
+
+    // 写入录制文件；写入失败时停止录制
+    fn record(&self, write: impl FnOnce(&mut AsciicastRecorder) -> Result<(), String>) {
+        let mut recorder = self.recorder.lock().unwrap();
+        if let Some(active) = recorder.as_mut() {
+            if let Err(e) = write(active) {
+                eprintln!("Recording of session {} stopped: {}", self.session_id, e);
+                *recorder = None;
+            }
+        }
+    }
+
+    // 推送当前帧
+    pub fn flush(&self) -> Result<()> {
+        let frame = std::mem::take(&mut *self.frame.lock().unwrap());
+        if frame.data.is_empty() {
+            return Ok(());
+        }
+
+        let end_offset = frame.offset + frame.data.len() as u64;
+        let output = TerminalOutput {
+            data: String::from_utf8_lossy(&frame.data).into_owned(),
+            offset: frame.offset,
+            end_offset,
+        };
+        self.flow.sent(end_offset);
+        self.app_handle
+            .emit(&format!("terminal-output-{}", self.session_id), output)?;
+        Ok(())
+    }
+
+    // 处理 OSC 7 和 OSC 133，offset 为 data 在输出流中的偏移量
+    fn handle_osc(&self, data: &str, offset: u64) {
+        for (start, end, payload) in osc_sequences(data) {
+            // OSC 7 格式: \x1b]7;file://hostname/path\x07 或 \x1b]7;file://hostname/path\x1b\\
+            if payload.starts_with("7;") {
+                if let Some(path) = parse_osc7_sequence(&data[start..end]) {
+                    println!("Detected directory change via OSC 7: {}", path);
+                    *self.current_dir.lock().unwrap() = PathBuf::from(path);
+                }
+                self.notify_ready(true);
+                continue;
+            }
+
+            let Some(mark) = payload.strip_prefix("133;").and_then(SemanticMark::parse) else {
+                continue;
+            };
+            // 命令输出从 C 标记之后开始，其他标记以标记本身的位置为界
+            let position = match mark {
+                SemanticMark::CommandStart { .. } => offset + end as u64,
+                _ => offset + start as u64,
+            };
+            let cwd = self
+                .current_dir
+                .lock()
+                .unwrap()
+                .to_string_lossy()
+                .to_string();
+            let finished = self.commands.lock().unwrap().mark(mark, position, &cwd);
+            if let Some(record) = finished {
+                if let Err(e) = self.app_handle.emit("terminal-command-finished", record) {
+                    eprintln!(
+                        "Failed to emit command event for session {}: {}",
+                        self.session_id, e
+                    );
+                }
+            }
+        }
+    }
+
+    // 注册就绪等待：needs_osc7 为 true 时等待 shell 钩子第一次上报 OSC 7
+    // 会话被移除时 cancel_ready 丢弃等待方，返回的 Receiver 随之断开
+    pub fn wait_ready(&self, needs_osc7: bool) -> mpsc::Receiver<()> {
+        let (notify, ready) = mpsc::channel();
+        *self.ready.lock().unwrap() = Some(ReadyWaiter { notify, needs_osc7 });
+        ready
+    }
+
+    fn cancel_ready(&self) {
+        self.ready.lock().unwrap().take();
+    }
+
+    fn notify_ready(&self, osc7: bool) {
+        let mut ready = self.ready.lock().unwrap();
+        if ready
+            .as_ref()
+            .is_some_and(|waiter| osc7 || !waiter.needs_osc7)
+        {
+            if let Some(waiter) = ready.take() {
+                let _ = waiter.notify.send(());
+            }
+        }
+    }
+
+    // 推送 terminal-ready-{id} 事件
+    pub fn ready(&self, timed_out: bool) {
+        let cwd = self
+            .current_dir
+            .lock()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        if let Err(e) = self.app_handle.emit(
+            &format!("terminal-ready-{}", self.session_id),
+            TerminalReady { cwd, timed_out },
+        ) {
+            eprintln!(
+                "Failed to emit ready event for session {}: {}",
+                self.session_id, e
+            );
+        }
+    }
+
+    // 推送 terminal-exit-{id} 事件
+    pub fn exit(&self, exit: TerminalExit) {
+        println!("Terminal session {} exited: {:?}", self.session_id, exit);
+        if let Err(e) = self
+            .app_handle
+            .emit(&format!("terminal-exit-{}", self.session_id), exit)
+        {
+            eprintln!(
+                "Failed to emit exit event for session {}: {}",
+                self.session_id, e
+            );
+        }
+    }
 }
 
 pub struct TerminalManager {
     sessions: Arc<Mutex<HashMap<u64, TerminalSession>>>,
+    host_key_prompts: HostKeyPrompts,
+    // 新会话的 scrollback 容量（字节）
+    scrollback_bytes: Arc<AtomicUsize>,
+    broadcast_groups: Arc<Mutex<BroadcastGroups>>,
 }
 
 impl Clone for TerminalManager {
     fn clone(&self) -> Self {
         Self {
             sessions: Arc::clone(&self.sessions),
+            host_key_prompts: self.host_key_prompts.clone(),
+            scrollback_bytes: Arc::clone(&self.scrollback_bytes),
+            broadcast_groups: Arc::clone(&self.broadcast_groups),
         }
     }
 }
@@ -30,7 +538,36 @@ impl TerminalManager {
     pub fn new() -> Self {
         Self {
             sessions: Arc::new(Mutex::new(HashMap::new())),
+            host_key_prompts: HostKeyPrompts::new(),
+            scrollback_bytes: Arc::new(AtomicUsize::new(DEFAULT_SCROLLBACK_BYTES)),
+            broadcast_groups: Arc::new(Mutex::new(BroadcastGroups::default())),
+        }
+    }
+
+    // 移除会话，同时退出它所在的广播组，并取消尚未完成的就绪等待
+    fn remove_session(&self, session_id: u64) -> Option<TerminalSession> {
+        self.broadcast_groups
+            .lock()
+            .unwrap()
+            .remove_session(session_id);
+        let session = self.sessions.lock().unwrap().remove(&session_id);
+        if let Some(session) = &session {
+            session.output.cancel_ready();
         }
+        session
+    }
+
+    fn session_output(&self, session_id: u64) -> Option<OutputSink> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions
+            .get(&session_id)
+            .map(|session| session.output.clone())
+    }
+
+    fn new_scrollback(&self) -> Arc<Mutex<Scrollback>> {
+        Arc::new(Mutex::new(Scrollback::new(
+            self.scrollback_bytes.load(Ordering::Relaxed),
+        )))
     }
 
     pub fn clone_for_reader(&self) -> Self {
@@ -40,31 +577,31 @@ impl TerminalManager {
     pub fn start_terminal(
         &self,
         session_id: u64,
-        _shell_type: Option<String>, // 忽略此参数，始终使用 zsh
+        shell_type: Option<String>,
+        options: TerminalOptions,
         app_handle: AppHandle,
     ) -> Result<()> {
+        // 会话已存在时应使用 attach_terminal 重新附加
+        if self.sessions.lock().unwrap().contains_key(&session_id) {
+            return Err(anyhow::anyhow!("Session {} already exists", session_id));
+        }
+
+        let initial_dir = options.working_dir()?;
+        let (cols, rows) = options.size();
         let pty_system = native_pty_system();
 
         // 创建 PTY pair
         let pair = pty_system.openpty(PtySize {
-            rows: 24,
-            cols: 80,
+            rows,
+            cols,
             pixel_width: 0,
             pixel_height: 0,
         })?;
 
-        // 默认使用 zsh（macOS 默认 shell）
-        let shell = if cfg!(target_os = "windows") {
-            "powershell.exe".to_string()
-        } else {
-            "/bin/zsh".to_string()
-        };
-
-        // 检查 shell 是否存在
-        if !std::path::Path::new(&shell).exists() {
-            eprintln!("Error: Shell not found at path: {}", shell);
-            return Err(anyhow::anyhow!("Shell not found: {}", shell));
-        }
+        // 解析要使用的 shell（找不到时回退到 $SHELL 和 /etc/shells）
+        let shell = resolve_shell(shell_type.as_deref())?;
+        let shell_kind = ShellKind::from_path(&shell);
+        let shell = shell.to_string_lossy().to_string();
 
         println!("Starting terminal with shell: {}", shell);
 
@@ -98,9 +635,8 @@ impl TerminalManager {
         if let Ok(home) = std::env::var("HOME") {
             cmd.env("HOME", home.clone());
         }
-        if let Ok(shell_env) = std::env::var("SHELL") {
-            cmd.env("SHELL", shell_env);
-        }
+        // SHELL 指向实际启动的 shell
+        cmd.env("SHELL", &shell);
         if let Ok(lang) = std::env::var("LANG") {
             cmd.env("LANG", lang);
         } else {
@@ -124,54 +660,42 @@ impl TerminalManager {
             }
         }
 
-        // 获取 home 目录用于配置文件路径
-        let home_dir = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        let initial_dir = PathBuf::from(&home_dir);
+        // 调用方指定的环境变量
+        for (key, value) in &options.env {
+            cmd.env(key, value);
+        }
+        for key in &options.env_remove {
+            cmd.env_remove(key);
+        }
 
         // 配置为交互式 shell 并自动加载所有配置文件
+        // bash / zsh / fish / sh / nu 都使用 -i 进入交互模式，-l 进入登录模式
         if !cfg!(target_os = "windows") {
-            if shell.contains("bash") {
-                // 新版 bash (Homebrew) 需要 -i 参数才能正常工作
-                cmd.arg("-i"); // 交互式 shell
-            } else if shell.contains("zsh") {
-                // zsh: 使用 -i 交互式模式会自动加载 .zshrc
-                cmd.arg("-i"); // 交互式 shell
-            } else if shell.contains("fish") {
-                // fish: 交互式模式会自动加载配置
-                cmd.arg("-i");
-            } else {
-                // 其他 shell：使用交互模式
-                cmd.arg("-i");
+            if options.login {
+                cmd.arg("-l");
             }
+            cmd.arg("-i");
         }
 
         // 设置工作目录
-        println!("Setting working directory to: {}", home_dir);
-        cmd.cwd(home_dir);
+        println!("Setting working directory to: {}", initial_dir.display());
+        cmd.cwd(&initial_dir);
 
         // 启动子进程
-        let _child = pair.slave.spawn_command(cmd)?;
+        let child = pair.slave.spawn_command(cmd)?;
+        let pid = child.process_id();
         println!(
-            "Successfully spawned shell process for session {}",
-            session_id
+            "Successfully spawned shell process for session {} (pid {:?})",
+            session_id, pid
         );
 
         // 获取读写器
         let mut reader = pair.master.try_clone_reader()?;
         let mut writer = pair.master.take_writer()?;
 
-        // 注入 OSC 7  cwd-reporting 功能
-        let osc7_command = if shell.contains("zsh") {
-            // For zsh, add a function to the precmd_functions array.
-            // This is executed before each prompt.
-            Some("precmd_functions+=('() { printf \"\\x1b]7;file://%s%s\\x07\" \"$(hostname)\" \"$PWD\" } ')\n".to_string())
-        } else if shell.contains("bash") {
-            // For bash, append to the PROMPT_COMMAND variable.
-            let cmd = "printf \"\\x1b]7;file://%s%s\\x07\" \"$(hostname)\" \"$PWD\"";
-            Some(format!("PROMPT_COMMAND=\"{};${{PROMPT_COMMAND}}\"\n", cmd))
-        } else {
-            None
-        };
+        // 注入 OSC 7  cwd-reporting 功能（每种 shell 的钩子语法不同）
+        let osc7_command = shell_kind.osc7_hook();
+        let needs_osc7 = osc7_command.is_some();
 
         if let Some(command) = osc7_command {
             if let Err(e) = writer.write_all(command.as_bytes()) {
@@ -179,110 +703,330 @@ impl TerminalManager {
             }
         }
 
-        // zsh 会自动加载 ~/.zshrc，无需手动执行
+        // 注入 OSC 133 语义标记（提示符、命令开始、命令结束及退出码）
+        if let Some(command) = shell_kind.osc133_hook() {
+            if let Err(e) = writer.write_all(command.as_bytes()) {
+                eprintln!("Failed to write OSC 133 command to PTY: {}", e);
+            }
+        }
 
         // 存储会话
+        let scrollback = self.new_scrollback();
+        let current_dir = Arc::new(Mutex::new(initial_dir));
+        let output = OutputSink::new(
+            session_id,
+            Arc::clone(&scrollback),
+            Arc::clone(&current_dir),
+            (cols, rows),
+            app_handle,
+        );
         {
             let mut sessions = self.sessions.lock().unwrap();
             sessions.insert(
                 session_id,
                 TerminalSession {
                     id: session_id,
-                    pair,
+                    backend: SessionBackend::Pty { pair, child },
                     writer: Box::new(writer),
-                    current_dir: Arc::new(Mutex::new(initial_dir)),
+                    current_dir,
+                    scrollback,
+                    output: output.clone(),
+                    shell: shell.clone(),
+                    pid,
+                    started_at: chrono::Local::now().to_rfc3339(),
+                    attached: 1,
                 },
             );
         }
 
-        // 等待 zsh 完全启动
-        std::thread::sleep(std::time::Duration::from_millis(300));
+        // 等待 shell 就绪（注入的钩子第一次上报 OSC 7，没有钩子时为第一段输出），
+        // 然后清除启动时的残留输出、执行初始化命令并推送 terminal-ready-{id}
+        // 在单独的线程中等待，不阻塞命令线程
+        // 线程不持有 OutputSink，会话结束时等待方被丢弃，recv 立即返回 Disconnected
+        let ready = output.wait_ready(needs_osc7);
+        let terminal_manager = self.clone();
+        let init_command = options.init_command.clone();
+        std::thread::spawn(move || {
+            let timed_out = match ready.recv_timeout(READY_TIMEOUT) {
+                Ok(()) => false,
+                Err(RecvTimeoutError::Timeout) => {
+                    eprintln!(
+                        "Session {} did not report readiness within {:?}",
+                        session_id, READY_TIMEOUT
+                    );
+                    true
+                }
+                // 会话在就绪前已结束
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
 
-        // 清除启动时的任何残留输出
-        let _ = self.write_terminal(session_id, "clear\n".to_string());
-        std::thread::sleep(std::time::Duration::from_millis(100));
+            // 超时期间会话可能已经结束
+            let Some(ready_output) = terminal_manager.session_output(session_id) else {
+                return;
+            };
+            let _ = terminal_manager.write_terminal(session_id, "clear\n".to_string());
+            if let Some(init_command) = init_command.as_deref() {
+                let init_command = init_command.trim_end();
+                if !init_command.is_empty() {
+                    let _ =
+                        terminal_manager.write_terminal(session_id, format!("{}\n", init_command));
+                }
+            }
+            ready_output.ready(timed_out);
+        });
 
-        // 启动读取任务
-        let app_handle_clone = app_handle.clone();
-        let terminal_manager_clone = Arc::new(self.clone_for_reader());
+        // 启动读取线程：只负责读取 PTY，未确认的输出过多时暂停读取
+        let (tx, rx) = mpsc::channel::<Vec<u8>>();
+        let flow = Arc::clone(&output.flow);
         std::thread::spawn(move || {
             let mut buf = [0u8; 8192];
             loop {
+                flow.wait_for_window();
                 match reader.read(&mut buf) {
-                    Ok(0) => {
-                        println!("Terminal session {} closed (EOF)", session_id);
-                        break;
-                    }
+                    Ok(0) => break,
                     Ok(n) => {
-                        let data = String::from_utf8_lossy(&buf[..n]).to_string();
-
-                        // 解析 OSC 7 序列来更新当前目录
-                        // OSC 7 格式: \x1b]7;file://hostname/path\x07 或 \x1b]7;file://hostname/path\x1b\\
-                        if data.contains("\x1b]7;") {
-                            if let Some(path) = parse_osc7_sequence(&data) {
-                                println!("Detected directory change via OSC 7: {}", path);
-                                let _ = terminal_manager_clone
-                                    .update_current_dir(session_id, PathBuf::from(&path));
-                            }
-                        }
-
-                        if let Err(e) =
-                            app_handle_clone.emit(&format!("terminal-output-{}", session_id), data)
-                        {
-                            eprintln!(
-                                "Failed to emit terminal output for session {}: {}",
-                                session_id, e
-                            );
+                        if tx.send(buf[..n].to_vec()).is_err() {
                             break;
                         }
                     }
                     Err(e) => {
-                        eprintln!("Error reading from terminal session {}: {}", session_id, e);
+                        // Linux 上 shell 退出后读取 PTY 会返回 EIO
+                        println!("Terminal session {} closed: {}", session_id, e);
                         break;
                     }
                 }
             }
         });
 
+        // 启动推送线程：把多次读取合并为一帧，每 OUTPUT_BUFFER_MS 或帧满时推送
+        let terminal_manager_clone = Arc::new(self.clone_for_reader());
+        std::thread::spawn(move || {
+            let frame_interval = Duration::from_millis(OUTPUT_BUFFER_MS);
+            // 当前帧的推送时间；没有待推送数据时阻塞等待下一次读取
+            let mut deadline: Option<Instant> = None;
+            loop {
+                let received = match deadline {
+                    Some(deadline) => {
+                        rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    }
+                    None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                let mut result = match received {
+                    Ok(bytes) => {
+                        deadline.get_or_insert_with(|| Instant::now() + frame_interval);
+                        output.push(&bytes)
+                    }
+                    Err(RecvTimeoutError::Timeout) => Ok(()),
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    deadline = None;
+                    result = result.and_then(|_| output.flush());
+                }
+                if let Err(e) = result {
+                    eprintln!(
+                        "Failed to emit terminal output for session {}: {}",
+                        session_id, e
+                    );
+                    break;
+                }
+            }
+
+            // 推送剩余输出后，清理自行退出的 shell 并推送退出事件
+            let _ = output.flush();
+            terminal_manager_clone.reap_session(session_id);
+        });
+
         Ok(())
     }
 
     pub fn write_terminal(&self, session_id: u64, data: String) -> Result<()> {
+        self.write_bytes(session_id, data.as_bytes())
+    }
+
+    // 原样写入字节（不要求是合法的 UTF-8）
+    pub fn write_bytes(&self, session_id: u64, data: &[u8]) -> Result<()> {
+        // 尝试写入，如果失败则静默忽略（PTY 可能已关闭）
+        if let Err(e) = self.try_write_bytes(session_id, data) {
+            eprintln!("Warning: Failed to write to terminal {}: {}", session_id, e);
+        }
+        // 不要抛出错误，因为 PTY 可能已经正常关闭
+        Ok(())
+    }
+
+    // 写入字节，会话不存在或写入失败时返回错误
+    fn try_write_bytes(&self, session_id: u64, data: &[u8]) -> Result<()> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get_mut(&session_id)
+            .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+        session
+            .output
+            .record(|recorder| recorder.input(&String::from_utf8_lossy(data)));
+        session.writer.write_all(data)?;
+        session.writer.flush()?;
+        Ok(())
+    }
+
+    // 加入广播组，组不存在时创建
+    pub fn join_broadcast_group(&self, group: &str, session_id: u64) -> Result<()> {
+        if group.trim().is_empty() {
+            return Err(anyhow::anyhow!("Broadcast group name is empty"));
+        }
+        if !self.sessions.lock().unwrap().contains_key(&session_id) {
+            return Err(anyhow::anyhow!("Session not found"));
+        }
+        self.broadcast_groups
+            .lock()
+            .unwrap()
+            .join(group.trim(), session_id);
+        Ok(())
+    }
+
+    pub fn leave_broadcast_group(&self, group: &str, session_id: u64) {
+        self.broadcast_groups
+            .lock()
+            .unwrap()
+            .leave(group.trim(), session_id);
+    }
+
+    pub fn list_broadcast_groups(&self) -> Vec<BroadcastGroup> {
+        self.broadcast_groups.lock().unwrap().list()
+    }
+
+    // 把同一段输入写入组内的每个会话，返回每个会话的写入结果
+    pub fn broadcast(&self, group: &str, data: &str) -> Result<Vec<BroadcastWrite>> {
+        let members = self
+            .broadcast_groups
+            .lock()
+            .unwrap()
+            .members(group.trim())
+            .ok_or_else(|| anyhow::anyhow!("Broadcast group not found: {}", group))?;
+
+        Ok(members
+            .into_iter()
+            .map(|session_id| BroadcastWrite {
+                session_id,
+                error: self
+                    .try_write_bytes(session_id, data.as_bytes())
+                    .err()
+                    .map(|e| e.to_string()),
+            })
+            .collect())
+    }
+
+    // 粘贴文本：程序开启了 bracketed paste 模式时加上 ESC[200~ / ESC[201~ 标记，
+    // 避免多行文本被 shell 逐行立即执行
+    pub fn paste_terminal(&self, session_id: u64, text: &str) -> Result<()> {
+        let bracketed = {
+            let sessions = self.sessions.lock().unwrap();
+            let session = sessions
+                .get(&session_id)
+                .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+            session.output.bracketed_paste.load(Ordering::Relaxed)
+        };
+        self.write_bytes(session_id, prepare_paste(text, bracketed).as_bytes())
+    }
+
+    pub fn resize_terminal(&self, session_id: u64, cols: u16, rows: u16) -> Result<()> {
         let mut sessions = self.sessions.lock().unwrap();
         if let Some(session) = sessions.get_mut(&session_id) {
-            // 尝试写入，如果失败则静默忽略（PTY 可能已关闭）
-            if let Err(e) = session.writer.write_all(data.as_bytes()) {
-                eprintln!("Warning: Failed to write to terminal {}: {}", session_id, e);
-                // 不要抛出错误，因为 PTY 可能已经正常关闭
-                return Ok(());
-            }
-            if let Err(e) = session.writer.flush() {
-                eprintln!("Warning: Failed to flush terminal {}: {}", session_id, e);
-                return Ok(());
+            session.output.resize(cols, rows);
+            match &session.backend {
+                SessionBackend::Pty { pair, .. } => pair.master.resize(PtySize {
+                    rows,
+                    cols,
+                    pixel_width: 0,
+                    pixel_height: 0,
+                })?,
+                SessionBackend::Ssh(channel) => channel.resize(cols, rows)?,
             }
         }
         Ok(())
     }
 
-    pub fn resize_terminal(&self, session_id: u64, cols: u16, rows: u16) -> Result<()> {
+    // 本地会话的 shell 进程号
+    pub fn shell_pid(&self, session_id: u64) -> Result<u32> {
         let sessions = self.sessions.lock().unwrap();
-        if let Some(session) = sessions.get(&session_id) {
-            session.pair.master.resize(PtySize {
-                rows,
-                cols,
-                pixel_width: 0,
-                pixel_height: 0,
-            })?;
+        let session = sessions
+            .get(&session_id)
+            .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+        match (&session.backend, session.pid) {
+            (SessionBackend::Pty { .. }, Some(pid)) => Ok(pid),
+            _ => Err(anyhow::anyhow!(
+                "Process info is only available for local sessions"
+            )),
         }
-        Ok(())
     }
 
-    pub fn close_terminal(&self, session_id: u64) -> Result<()> {
-        let mut sessions = self.sessions.lock().unwrap();
-        sessions.remove(&session_id);
+    // 会话中正在前台运行的任务（shell 空闲时为空）
+    pub fn foreground_job(&self, session_id: u64) -> Option<ForegroundJob> {
+        process_info::foreground_job(self.shell_pid(session_id).ok()?)
+    }
+
+    // 关闭会话：本地 shell 先收到 SIGHUP，宽限期后仍未退出则 SIGKILL
+    // 前台有任务在运行时，除非 force 为 true，否则拒绝关闭并返回提示
+    pub fn close_terminal(&self, session_id: u64, force: bool) -> Result<()> {
+        if let Some(job) = self.foreground_job(session_id) {
+            if !force {
+                return Err(anyhow::anyhow!(
+                    "Session {} is running `{}` (pgrp {}); close it with force to kill the job",
+                    session_id,
+                    job.cmdline,
+                    job.pgrp
+                ));
+            }
+            eprintln!(
+                "Closing session {} while `{}` is still running",
+                session_id, job.cmdline
+            );
+        }
+
+        let session = self.remove_session(session_id);
+        let Some(session) = session else {
+            return Ok(());
+        };
+
+        session.output.flow.close();
+        match session.backend {
+            SessionBackend::Pty { pair, mut child } => {
+                let output = session.output;
+                std::thread::spawn(move || {
+                    let status = terminate_child(child.as_mut(), pair, CLOSE_GRACE_PERIOD);
+                    match status {
+                        Ok(status) => output.exit(TerminalExit::from(&status)),
+                        Err(e) => {
+                            eprintln!("Failed to terminate session {}: {}", session_id, e);
+                            output.exit(TerminalExit::default());
+                        }
+                    }
+                });
+            }
+            // 远程 shell 的退出事件由 SSH 任务结束时推送
+            SessionBackend::Ssh(channel) => channel.close(),
+        }
         Ok(())
     }
 
+    // shell 自行退出后由读取线程调用；会话已被 close_terminal 移除时不做任何事
+    fn reap_session(&self, session_id: u64) {
+        let session = self.remove_session(session_id);
+        if let Some(TerminalSession {
+            backend: SessionBackend::Pty { mut child, .. },
+            output,
+            ..
+        }) = session
+        {
+            match child.wait() {
+                Ok(status) => output.exit(TerminalExit::from(&status)),
+                Err(e) => {
+                    eprintln!("Failed to wait for session {}: {}", session_id, e);
+                    output.exit(TerminalExit::default());
+                }
+            }
+        }
+    }
+
     pub fn get_current_dir(&self, session_id: u64) -> Result<String> {
         let sessions = self.sessions.lock().unwrap();
         if let Some(session) = sessions.get(&session_id) {
@@ -293,166 +1037,779 @@ impl TerminalManager {
         }
     }
 
-    pub fn update_current_dir(&self, session_id: u64, new_dir: PathBuf) -> Result<()> {
+    // 列出所有会话（包括没有前端附加、在后台运行的会话）
+    pub fn list_terminals(&self) -> Vec<TerminalInfo> {
         let sessions = self.sessions.lock().unwrap();
-        if let Some(session) = sessions.get(&session_id) {
-            let mut current_dir = session.current_dir.lock().unwrap();
-            *current_dir = new_dir;
-            println!(
-                "Updated current_dir for session {} to: {:?}",
-                session_id, *current_dir
-            );
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("Session not found"))
-        }
+        let mut list: Vec<TerminalInfo> = sessions
+            .values()
+            .map(|session| TerminalInfo {
+                id: session.id,
+                kind: match session.backend {
+                    SessionBackend::Pty { .. } => "local",
+                    SessionBackend::Ssh(_) => "ssh",
+                },
+                shell: session.shell.clone(),
+                cwd: session
+                    .current_dir
+                    .lock()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string(),
+                pid: session.pid,
+                started_at: session.started_at.clone(),
+                attached: session.attached > 0,
+            })
+            .collect();
+        list.sort_by_key(|info| info.id);
+        list
     }
 
-    // SSH 连接方法
-    pub fn start_ssh_terminal(
-        &self,
-        session_id: u64,
-        host: String,
-        port: u16,
-        username: String,
-        password: Option<String>,
-        app_handle: AppHandle,
-    ) -> Result<()> {
-        let pty_system = native_pty_system();
+    // 重新附加到已有会话，返回 since_offset 之后的历史输出供前端回放
+    pub fn attach_terminal(&self, session_id: u64, since_offset: u64) -> Result<ScrollbackChunk> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get_mut(&session_id)
+            .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+        session.attached += 1;
+        session.output.flow.reset();
+        let chunk = session.scrollback.lock().unwrap().since(since_offset);
+        Ok(chunk)
+    }
 
-        // 创建 PTY pair
-        let pair = pty_system.openpty(PtySize {
-            rows: 24,
-            cols: 80,
-            pixel_width: 0,
-            pixel_height: 0,
-        })?;
+    // 前端断开但保留会话，shell 继续在后台运行
+    pub fn detach_terminal(&self, session_id: u64) -> Result<()> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get_mut(&session_id)
+            .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+        session.attached = session.attached.saturating_sub(1);
+        session.output.flow.reset();
+        Ok(())
+    }
 
-        println!("Starting SSH connection to {}@{}:{}", username, host, port);
+    // 前端确认已处理到 offset 的输出，用于流量控制
+    pub fn ack_output(&self, session_id: u64, offset: u64) -> Result<()> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(&session_id)
+            .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+        session.output.flow.ack(offset);
+        Ok(())
+    }
 
-        // 如果提供了密码，使用 expect 脚本自动输入密码
-        let command_to_run = if let Some(pwd) = password {
-            // 创建临时 expect 脚本文件
-            let script_content = format!(
-                "#!/usr/bin/expect -f\nset timeout 30\nspawn ssh -o StrictHostKeyChecking=no -p {} {}@{}\nexpect {{\n    \"*password:\" {{send \"{}\\r\"; exp_continue}}\n    \"*Password:\" {{send \"{}\\r\"; exp_continue}}\n    eof\n}}\ninteract\n",
-                port, username, host, pwd, pwd
-            );
+    // 获取 since_offset 之后仍保留在 scrollback 中的输出
+    pub fn get_scrollback(&self, session_id: u64, since_offset: u64) -> Result<ScrollbackChunk> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(&session_id)
+            .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+        let scrollback = session.scrollback.lock().unwrap();
+        Ok(scrollback.since(since_offset))
+    }
 
-            // 写入临时文件
-            let temp_script_path = format!("/tmp/ssh_expect_{}.exp", session_id);
-            if let Err(e) = std::fs::write(&temp_script_path, script_content) {
-                eprintln!("Failed to write expect script: {}", e);
-                return Err(anyhow::anyhow!("Failed to create expect script: {}", e));
-            }
+    // 在去掉转义序列的 scrollback 中搜索，session_id 为空时搜索所有会话
+    pub fn search(
+        &self,
+        session_id: Option<u64>,
+        pattern: &str,
+        regex: bool,
+        case_sensitive: bool,
+    ) -> Result<Vec<SearchMatch>> {
+        let query = SearchQuery::new(pattern, regex, case_sensitive).map_err(anyhow::Error::msg)?;
 
-            // 设置文件权限为可执行
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                if let Err(e) = std::fs::set_permissions(&temp_script_path, std::fs::Permissions::from_mode(0o755)) {
-                    eprintln!("Failed to set script permissions: {}", e);
+        let mut targets: Vec<(u64, Arc<Mutex<Scrollback>>)> = {
+            let sessions = self.sessions.lock().unwrap();
+            match session_id {
+                Some(id) => {
+                    let session = sessions
+                        .get(&id)
+                        .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+                    vec![(id, Arc::clone(&session.scrollback))]
                 }
+                None => sessions
+                    .iter()
+                    .map(|(id, session)| (*id, Arc::clone(&session.scrollback)))
+                    .collect(),
             }
+        };
+        targets.sort_by_key(|(id, _)| *id);
 
-            println!("Created expect script at: {}", temp_script_path);
-            println!("Script will auto-enter password for SSH connection");
-
-            // 执行 expect 脚本
-            format!("expect {}", temp_script_path)
-        } else {
-            // 没有密码，直接使用 ssh 命令（密钥认证）
-            format!("ssh -o StrictHostKeyChecking=no -p {} {}@{}", port, username, host)
-        };
+        let mut matches = Vec::new();
+        for (id, scrollback) in targets {
+            // 复制一份再搜索，避免长时间阻塞输出线程
+            let (start_offset, data) = scrollback.lock().unwrap().snapshot();
+            let limit = MAX_SEARCH_MATCHES - matches.len();
+            matches.extend(query.search(id, start_offset, &data, limit));
+            if matches.len() >= MAX_SEARCH_MATCHES {
+                break;
+            }
+        }
+        Ok(matches)
+    }
 
-        // 使用 shell 来执行命令
-        let shell = if cfg!(target_os = "windows") {
-            "powershell.exe"
-        } else {
-            "/bin/sh"
-        };
+    // 开始将会话录制为 asciicast v2 文件，record_input 为 false 时只录制输出
+    pub fn start_recording(
+        &self,
+        session_id: u64,
+        path: PathBuf,
+        record_input: bool,
+        overwrite: bool,
+    ) -> Result<PathBuf> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(&session_id)
+            .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+        let mut recorder = session.output.recorder.lock().unwrap();
+        if let Some(active) = recorder.as_ref() {
+            return Err(anyhow::anyhow!(
+                "Session {} is already being recorded to {}",
+                session_id,
+                active.path().display()
+            ));
+        }
 
-        let mut cmd = CommandBuilder::new(shell);
+        let (cols, rows) = session.output.size();
+        let title = format!("Session {}", session_id);
+        let started = AsciicastRecorder::create(
+            &path,
+            cols,
+            rows,
+            Some(title),
+            &session.shell,
+            record_input,
+            overwrite,
+        )
+        .map_err(anyhow::Error::msg)?;
+        let path = started.path().to_path_buf();
+        println!("Recording session {} to {}", session_id, path.display());
+        *recorder = Some(started);
+        Ok(path)
+    }
 
-        // 设置环境变量
-        cmd.env("TERM", "xterm-256color");
-        cmd.env("COLORTERM", "truecolor");
+    // 停止录制，返回录制文件路径
+    pub fn stop_recording(&self, session_id: u64) -> Result<PathBuf> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(&session_id)
+            .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+        let recorder = session.output.recorder.lock().unwrap().take();
+        let recorder = recorder
+            .ok_or_else(|| anyhow::anyhow!("Session {} is not being recorded", session_id))?;
+        recorder.finish().map_err(anyhow::Error::msg)
+    }
 
-        // 传递环境变量
-        if let Ok(path) = std::env::var("PATH") {
-            cmd.env("PATH", path);
-        }
-        if let Ok(user) = std::env::var("USER") {
-            cmd.env("USER", user);
-        }
-        if let Ok(home) = std::env::var("HOME") {
-            cmd.env("HOME", home.clone());
+    // 通过 terminal-output-{id} 事件回放录制文件，speed 为播放倍速
+    // 两个事件之间的停顿最多 2 秒，回放结束后推送 terminal-exit-{id}
+    pub fn replay_recording(
+        &self,
+        session_id: u64,
+        path: PathBuf,
+        speed: f64,
+        app_handle: AppHandle,
+    ) -> Result<()> {
+        if self.sessions.lock().unwrap().contains_key(&session_id) {
+            return Err(anyhow::anyhow!("Session {} already exists", session_id));
         }
-        if let Ok(lang) = std::env::var("LANG") {
-            cmd.env("LANG", lang);
-        } else {
-            cmd.env("LANG", "en_US.UTF-8");
+        if !speed.is_finite() || speed <= 0.0 {
+            return Err(anyhow::anyhow!("Invalid replay speed: {}", speed));
         }
+        let (_, events) = recording::read_cast(&path).map_err(anyhow::Error::msg)?;
 
-        // 使用 -c 参数执行命令
-        if cfg!(target_os = "windows") {
-            cmd.arg("-Command");
-        } else {
-            cmd.arg("-c");
+        tokio::spawn(async move {
+            const MAX_IDLE: f64 = 2.0;
+            let event = format!("terminal-output-{}", session_id);
+            let mut offset = 0u64;
+            let mut last_time = 0.0;
+            for cast_event in events.into_iter().filter(|e| e.kind == "o") {
+                let delay = (cast_event.time - last_time).clamp(0.0, MAX_IDLE) / speed;
+                last_time = cast_event.time;
+                tokio::time::sleep(Duration::from_secs_f64(delay)).await;
+
+                let end_offset = offset + cast_event.data.len() as u64;
+                let output = TerminalOutput {
+                    data: cast_event.data,
+                    offset,
+                    end_offset,
+                };
+                offset = end_offset;
+                if let Err(e) = app_handle.emit(&event, output) {
+                    eprintln!(
+                        "Failed to emit replay output for session {}: {}",
+                        session_id, e
+                    );
+                    return;
+                }
+            }
+            let _ = app_handle.emit(
+                &format!("terminal-exit-{}", session_id),
+                TerminalExit {
+                    code: Some(0),
+                    signal: None,
+                },
+            );
+        });
+        Ok(())
+    }
+
+    // 会话当前的屏幕内容
+    pub fn get_screen(&self, session_id: u64) -> Result<TerminalScreen> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(&session_id)
+            .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+        Ok(session.output.screen())
+    }
+
+    // 会话中记录的命令（来自 OSC 133 标记）
+    pub fn list_commands(&self, session_id: u64) -> Result<Vec<CommandRecord>> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(&session_id)
+            .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+        let commands = session.output.commands.lock().unwrap();
+        Ok(commands.list())
+    }
+
+    // 最近一条失败的命令及其输出，供 AI 分析
+    pub fn last_failed_command(&self, session_id: u64) -> Result<Option<CommandWithOutput>> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(&session_id)
+            .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+        let record = session.output.commands.lock().unwrap().last_failed();
+        Ok(record.map(|record| CommandWithOutput {
+            output: command_output(&session.scrollback.lock().unwrap(), &record),
+            record,
+        }))
+    }
+
+    // 命令的输出文本（已被 scrollback 丢弃的部分不包含在内）
+    pub fn get_command_output(&self, session_id: u64, command_id: u64) -> Result<String> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(&session_id)
+            .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+        let record = session
+            .output
+            .commands
+            .lock()
+            .unwrap()
+            .get(command_id)
+            .ok_or_else(|| anyhow::anyhow!("Command {} not found", command_id))?;
+        let scrollback = session.scrollback.lock().unwrap();
+        Ok(command_output(&scrollback, &record))
+    }
+
+    // 设置 scrollback 容量，同时作用于已有会话
+    pub fn set_scrollback_size(&self, bytes: usize) {
+        self.scrollback_bytes.store(bytes, Ordering::Relaxed);
+        let sessions = self.sessions.lock().unwrap();
+        for session in sessions.values() {
+            session.scrollback.lock().unwrap().set_capacity(bytes);
         }
-        cmd.arg(&command_to_run);
+    }
 
-        // 启动子进程
-        let _child = pair.slave.spawn_command(cmd)?;
-        println!("Successfully spawned SSH process for session {}", session_id);
+    // SSH 连接方法（基于 russh 的原生 SSH 会话）
+    pub async fn start_ssh_terminal(
+        &self,
+        session_id: u64,
+        options: SshConnectOptions,
+        app_handle: AppHandle,
+    ) -> Result<()> {
+        // 与 start_terminal 相同，会话已存在时应使用 attach_terminal 重新附加
+        if self.sessions.lock().unwrap().contains_key(&session_id) {
+            return Err(anyhow::anyhow!("Session {} already exists", session_id));
+        }
 
-        // 获取读写器
-        let mut reader = pair.master.try_clone_reader()?;
-        let writer = pair.master.take_writer()?;
+        println!(
+            "Starting SSH connection to {}@{}:{}",
+            options.username, options.host, options.port
+        );
 
-        // 存储会话
+        let scrollback = self.new_scrollback();
         let home_dir = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+        let current_dir = Arc::new(Mutex::new(PathBuf::from(home_dir)));
+        let output = OutputSink::new(
+            session_id,
+            Arc::clone(&scrollback),
+            Arc::clone(&current_dir),
+            (options.cols, options.rows),
+            app_handle.clone(),
+        );
+        let (channel, task) = ssh::open_shell(
+            session_id,
+            &options,
+            self.host_key_prompts.clone(),
+            output.clone(),
+            app_handle,
+        )
+        .await?;
+        println!("SSH session {} established", session_id);
+
+        // 存储会话；连接期间同一编号可能已被另一次调用占用，此时关闭新连接
         {
             let mut sessions = self.sessions.lock().unwrap();
+            if sessions.contains_key(&session_id) {
+                channel.close();
+                return Err(anyhow::anyhow!("Session {} already exists", session_id));
+            }
             sessions.insert(
                 session_id,
                 TerminalSession {
                     id: session_id,
-                    pair,
-                    writer: Box::new(writer),
-                    current_dir: Arc::new(Mutex::new(PathBuf::from(home_dir))),
+                    writer: Box::new(channel.writer()),
+                    backend: SessionBackend::Ssh(channel),
+                    current_dir,
+                    scrollback,
+                    output: output.clone(),
+                    shell: format!("{}@{}:{}", options.username, options.host, options.port),
+                    pid: None,
+                    started_at: chrono::Local::now().to_rfc3339(),
+                    attached: 1,
                 },
             );
         }
+        // 远程 shell 没有注入钩子，通道打开即视为就绪
+        output.ready(false);
 
-        // 启动读取任务
-        let app_handle_clone = app_handle.clone();
-        std::thread::spawn(move || {
-            let mut buf = [0u8; 8192];
-            loop {
-                match reader.read(&mut buf) {
-                    Ok(0) => {
-                        println!("SSH session {} closed (EOF)", session_id);
-                        break;
-                    }
-                    Ok(n) => {
-                        let data = String::from_utf8_lossy(&buf[..n]).to_string();
-                        if let Err(e) =
-                            app_handle_clone.emit(&format!("terminal-output-{}", session_id), data)
-                        {
-                            eprintln!("Failed to emit SSH output for session {}: {}", session_id, e);
+        // 远程 shell 退出，或连接断开且重连失败后清理会话
+        let terminal_manager = self.clone();
+        tokio::spawn(async move {
+            let exit = task.await.unwrap_or_default();
+            terminal_manager.remove_session(session_id);
+            output.exit(exit);
+        });
+
+        Ok(())
+    }
+
+    // SSH 会话的端口转发
+    fn ssh_forwards(&self, session_id: u64) -> Result<SshForwards> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(&session_id)
+            .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+        match &session.backend {
+            SessionBackend::Ssh(channel) => Ok(channel.forwards()),
+            SessionBackend::Pty { .. } => Err(anyhow::anyhow!(
+                "Port forwarding is only available for SSH sessions"
+            )),
+        }
+    }
+
+    pub fn list_forwards(&self, session_id: u64) -> Result<Vec<ForwardStatus>> {
+        Ok(self.ssh_forwards(session_id)?.list())
+    }
+
+    // 在已连接的会话上新增转发，监听失败时返回 failed 状态
+    pub async fn add_forward(&self, session_id: u64, spec: ForwardSpec) -> Result<ForwardStatus> {
+        self.ssh_forwards(session_id)?.add(spec).await
+    }
+
+    pub async fn remove_forward(&self, session_id: u64, forward_id: u64) -> Result<()> {
+        self.ssh_forwards(session_id)?.remove(forward_id).await
+    }
+
+    // SFTP 连接与终端使用同一个提示表（按 kind 区分），由 respond_ssh_hostkey 统一应答
+    pub fn host_key_prompts(&self) -> HostKeyPrompts {
+        self.host_key_prompts.clone()
+    }
+
+    // 响应 ssh-hostkey-prompt：接受（写入 known_hosts）或拒绝主机密钥
+    pub fn respond_host_key(
+        &self,
+        kind: HostKeyPromptKind,
+        session_id: u64,
+        accept: bool,
+    ) -> Result<()> {
+        self.host_key_prompts.respond(kind, session_id, accept)
+    }
+}
+
+// 从 scrollback 中取出命令的输出，运行中的命令取到当前位置
+fn command_output(scrollback: &Scrollback, record: &CommandRecord) -> String {
+    let end = record.output_end.unwrap_or_else(|| scrollback.end_offset());
+    scrollback.range(record.output_start, end).data
+}
+
+// write_terminal_bytes 的参数：base64 字符串或字节数组
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum TerminalBytes {
+    Base64(String),
+    Bytes(Vec<u8>),
+}
+
+impl TerminalBytes {
+    pub fn into_bytes(self) -> Result<Vec<u8>> {
+        match self {
+            TerminalBytes::Base64(encoded) => Ok(BASE64.decode(encoded.trim())?),
+            TerminalBytes::Bytes(bytes) => Ok(bytes),
+        }
+    }
+}
+
+// 查找输出中最后一次 DECSET/DECRST 2004（ESC[?2004h / ESC[?2004l），
+// 返回 bracketed paste 模式的新状态
+fn bracketed_paste_change(data: &[u8]) -> Option<bool> {
+    let mut state = None;
+    let mut rest = data;
+    while let Some(pos) = rest.windows(3).position(|w| w == b"\x1b[?") {
+        let params = &rest[pos + 3..];
+        let Some(end) = params
+            .iter()
+            .position(|b| !b.is_ascii_digit() && *b != b';')
+        else {
+            break;
+        };
+        let enable = match params[end] {
+            b'h' => Some(true),
+            b'l' => Some(false),
+            _ => None,
+        };
+        // 一个序列可以同时设置多个模式，例如 ESC[?1049;2004h
+        if let Some(enable) = enable {
+            if params[..end].split(|b| *b == b';').any(|p| p == b"2004") {
+                state = Some(enable);
+            }
+        }
+        rest = &params[end..];
+    }
+    state
+}
+
+// 粘贴文本的预处理：换行统一为回车（与真实终端一致），
+// bracketed paste 模式下去掉文本中的结束标记，防止粘贴内容提前结束粘贴
+fn prepare_paste(text: &str, bracketed: bool) -> String {
+    let text = text.replace("\r\n", "\r").replace('\n', "\r");
+    if bracketed {
+        format!(
+            "\x1b[200~{}\x1b[201~",
+            text.replace("\x1b[201~", "").replace("\x1b[200~", "")
+        )
+    } else {
+        text
+    }
+}
+
+// 跨读取边界的流式解码器
+// PTY 每次读取的边界是任意的，多字节 UTF-8 字符和转义序列都可能被截断；
+// 解码器把末尾不完整的部分留到下一次读取，保证推送出去的每一段都是完整的
+struct OutputDecoder {
+    pending: Vec<u8>,
+}
+
+// 最多保留的不完整数据，超过后直接推送，避免异常输出导致数据一直积压
+const MAX_PENDING_BYTES: usize = 4096;
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+impl OutputDecoder {
+    fn new() -> Self {
+        Self {
+            pending: Vec::new(),
+        }
+    }
+
+    // 输入新读取的字节，返回可以安全推送的完整字节
+    fn feed(&mut self, bytes: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(bytes);
+        let mut split = complete_prefix_len(&self.pending);
+        if self.pending.len() - split > MAX_PENDING_BYTES {
+            split = self.pending.len();
+        }
+        self.pending.drain(..split).collect()
+    }
+}
+
+// 返回 buf 中可以安全推送的前缀长度
+fn complete_prefix_len(buf: &[u8]) -> usize {
+    if let Some(start) = incomplete_escape_start(buf) {
+        // ESC 是 ASCII，之前的字节不会属于一个未完成的 UTF-8 字符
+        return start;
+    }
+    buf.len() - incomplete_utf8_suffix_len(buf)
+}
+
+// 查找末尾未结束的转义序列的起始位置
+fn incomplete_escape_start(buf: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    while i < buf.len() {
+        if buf[i] != ESC {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let Some(&kind) = buf.get(i + 1) else {
+            return Some(start);
+        };
+        match kind {
+            // CSI：ESC [ 参数 中间字节 终止字节(0x40..=0x7E)
+            b'[' => match buf[i + 2..].iter().position(|b| (0x40..=0x7e).contains(b)) {
+                Some(end) => i += 2 + end + 1,
+                None => return Some(start),
+            },
+            // OSC / DCS / APC / PM / SOS：以 BEL 或 ST(ESC \\) 结束
+            b']' | b'P' | b'_' | b'^' | b'X' => {
+                let mut j = i + 2;
+                loop {
+                    match buf.get(j) {
+                        None => return Some(start),
+                        Some(&BEL) => {
+                            i = j + 1;
                             break;
                         }
-                    }
-                    Err(e) => {
-                        eprintln!("Error reading from SSH session {}: {}", session_id, e);
-                        break;
+                        Some(&ESC) => match buf.get(j + 1) {
+                            None => return Some(start),
+                            Some(b'\\') => {
+                                i = j + 2;
+                                break;
+                            }
+                            // 序列被新的 ESC 打断，从新的 ESC 继续解析
+                            Some(_) => {
+                                i = j;
+                                break;
+                            }
+                        },
+                        Some(_) => j += 1,
                     }
                 }
             }
-        });
+            // 其他 ESC 序列：可选的中间字节(0x20..=0x2F) 加一个终止字节
+            _ => {
+                let mut j = i + 1;
+                while j < buf.len() && (0x20..=0x2f).contains(&buf[j]) {
+                    j += 1;
+                }
+                if j >= buf.len() {
+                    return Some(start);
+                }
+                i = j + 1;
+            }
+        }
+    }
+    None
+}
 
-        Ok(())
+// 末尾不完整的 UTF-8 字符的字节数
+fn incomplete_utf8_suffix_len(buf: &[u8]) -> usize {
+    for k in 1..=buf.len().min(3) {
+        let byte = buf[buf.len() - k];
+        if byte & 0xc0 == 0x80 {
+            // 后续字节，继续向前查找首字节
+            continue;
+        }
+        let needed = match byte {
+            0xf0..=0xf7 => 4,
+            0xe0..=0xef => 3,
+            0xc0..=0xdf => 2,
+            _ => 1,
+        };
+        return if needed > k { k } else { 0 };
     }
+    0
+}
+
+// 结束 shell 子进程：先发送 SIGHUP 并关闭 PTY，宽限期后仍在运行则发送 SIGKILL
+fn terminate_child(
+    child: &mut (dyn Child + Send + Sync),
+    pair: PtyPair,
+    grace: Duration,
+) -> std::io::Result<ExitStatus> {
+    // Unix 上 ChildKiller::kill 发送 SIGHUP；Windows 上直接结束进程
+    if let Err(e) = child.clone_killer().kill() {
+        eprintln!("Failed to send SIGHUP to shell: {}", e);
+    }
+    drop(pair);
+
+    let deadline = Instant::now() + grace;
+    while Instant::now() < deadline {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    #[cfg(unix)]
+    if let Some(pid) = child.process_id() {
+        println!("Shell {} ignored SIGHUP, sending SIGKILL", pid);
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGKILL);
+        }
+    }
+    child.wait()
+}
+
+// 支持的 shell 类型，用于决定启动参数和 OSC 7 钩子
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShellKind {
+    Bash,
+    Zsh,
+    Fish,
+    Sh,
+    Nu,
+    PowerShell,
+    Other,
+}
+
+impl ShellKind {
+    fn from_path(path: &Path) -> Self {
+        let name = path
+            .file_stem()
+            .map(|n| n.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match name.as_str() {
+            "bash" => ShellKind::Bash,
+            "zsh" => ShellKind::Zsh,
+            "fish" => ShellKind::Fish,
+            "sh" | "dash" | "ash" => ShellKind::Sh,
+            "nu" => ShellKind::Nu,
+            "powershell" | "pwsh" => ShellKind::PowerShell,
+            _ => ShellKind::Other,
+        }
+    }
+
+    // 生成向终端报告当前目录（OSC 7）的钩子命令
+    fn osc7_hook(self) -> Option<String> {
+        match self {
+            // zsh: 添加到 precmd_functions，每次显示提示符前执行
+            ShellKind::Zsh => Some(
+                "precmd_functions+=('() { printf \"\\x1b]7;file://%s%s\\x07\" \"$(hostname)\" \"$PWD\" } ')\n"
+                    .to_string(),
+            ),
+            // bash: 追加到 PROMPT_COMMAND
+            ShellKind::Bash => {
+                let cmd = "printf \"\\x1b]7;file://%s%s\\x07\" \"$(hostname)\" \"$PWD\"";
+                Some(format!("PROMPT_COMMAND=\"{};${{PROMPT_COMMAND}}\"\n", cmd))
+            }
+            // fish: 监听 PWD 变量变化，并立即报告一次
+            ShellKind::Fish => Some(
+                "function __huaan_osc7 --on-variable PWD; printf '\\e]7;file://%s%s\\a' (hostname) $PWD; end; __huaan_osc7\n"
+                    .to_string(),
+            ),
+            // nu: 注册 env_change.PWD 钩子
+            ShellKind::Nu => Some(
+                "$env.config.hooks.env_change.PWD = ($env.config.hooks.env_change.PWD? | default [] | append {|before, after| print -n $\"\\e]7;file://(sys host | get hostname)($after)\\u{7}\" })\n"
+                    .to_string(),
+            ),
+            // sh: 没有提示符钩子，把 OSC 7 写进 PS1；POSIX 规定每次显示提示符前
+            // 对 PS1 做参数展开，因此 ${PWD} 总是当前目录（转义字符由 printf 生成）
+            ShellKind::Sh => Some(
+                concat!(
+                    r#"PS1="$(printf '\033]7;file://%s' "$(hostname)")"'${PWD}'"$(printf '\007')${PS1}""#,
+                    "\n"
+                )
+                .to_string(),
+            ),
+            // PowerShell 和其他 shell 没有可靠的提示符钩子，就绪判断退回到第一段输出
+            ShellKind::PowerShell | ShellKind::Other => None,
+        }
+    }
+
+    // 生成 OSC 133 语义标记钩子：提示符前报告上一条命令的退出码（D）和提示符开始（A），
+    // 命令执行前报告命令开始（C）及命令文本；没有运行中命令的 D 会被后端忽略
+    fn osc133_hook(self) -> Option<&'static str> {
+        match self {
+            // zsh: precmd 放在最前面以获取上一条命令的 $?
+            ShellKind::Zsh => Some(concat!(
+                r#"__huaan_precmd() { printf '\e]133;D;%s\a\e]133;A\a' "$?"; }; "#,
+                r#"__huaan_preexec() { printf '\e]133;C;cmdline=%s\a' "${1//[[:cntrl:]]/ }"; }; "#,
+                r#"precmd_functions=(__huaan_precmd $precmd_functions); preexec_functions+=(__huaan_preexec)"#,
+                "\n"
+            )),
+            // bash: 没有 preexec，用 DEBUG trap 模拟，只在提示符后的第一条命令触发；
+            // 与 bash-preexec 相同，标志在 PROMPT_COMMAND 的最后一步才设置，
+            // 避免 PROMPT_COMMAND 中的其他命令（如 OSC 7 的 printf）被当成用户命令
+            ShellKind::Bash => Some(concat!(
+                r#"__huaan_precmd() { printf '\e]133;D;%s\a\e]133;A\a' "$?"; __huaan_prompt=; }; "#,
+                r#"__huaan_preexec() { [[ -z $__huaan_prompt || $BASH_COMMAND == __huaan_precmd* ]] && return; __huaan_prompt=; "#,
+                r#"local cmd; cmd=$(HISTTIMEFORMAT= builtin history 1); cmd=${cmd#*[0-9]  }; "#,
+                r#"printf '\e]133;C;cmdline=%s\a' "${cmd//[[:cntrl:]]/ }"; }; "#,
+                r#"trap '__huaan_preexec' DEBUG; PROMPT_COMMAND=$'__huaan_precmd\n'"${PROMPT_COMMAND}"$'\n__huaan_prompt=1'"#,
+                "\n"
+            )),
+            // fish: 使用 fish_preexec / fish_postexec / fish_prompt 事件
+            ShellKind::Fish => Some(concat!(
+                r#"function __huaan_preexec --on-event fish_preexec; printf '\e]133;C;cmdline=%s\a' (string replace -ra '[[:cntrl:]]' ' ' -- $argv); end; "#,
+                r#"function __huaan_postexec --on-event fish_postexec; printf '\e]133;D;%s\a' $status; end; "#,
+                r#"function __huaan_prompt --on-event fish_prompt; printf '\e]133;A\a'; end"#,
+                "\n"
+            )),
+            // nu: 注册 pre_prompt / pre_execution 钩子
+            ShellKind::Nu => Some(concat!(
+                r#"$env.config.hooks.pre_prompt = ($env.config.hooks.pre_prompt? | default [] | append {|| print -n $"\e]133;D;($env.LAST_EXIT_CODE)\u{7}\e]133;A\u{7}" }); "#,
+                r#"$env.config.hooks.pre_execution = ($env.config.hooks.pre_execution? | default [] | append {|| print -n $"\e]133;C;cmdline=(commandline | str replace -a -r '[\x00-\x1f]' ' ')\u{7}" })"#,
+                "\n"
+            )),
+            ShellKind::Sh | ShellKind::PowerShell | ShellKind::Other => None,
+        }
+    }
+}
+
+// 解析 shell_type 参数为可执行文件路径
+// 支持 bash / zsh / fish / sh / nu 或绝对路径；找不到时依次回退到 $SHELL 和 /etc/shells
+fn resolve_shell(shell_type: Option<&str>) -> Result<PathBuf> {
+    if cfg!(target_os = "windows") {
+        return Ok(PathBuf::from(shell_type.unwrap_or("powershell.exe")));
+    }
+
+    if let Some(requested) = shell_type.map(str::trim).filter(|s| !s.is_empty()) {
+        if let Some(path) = find_shell(requested) {
+            return Ok(path);
+        }
+        eprintln!(
+            "Warning: Requested shell '{}' not found, falling back to default shell",
+            requested
+        );
+    }
+
+    // 回退 1：用户的 $SHELL
+    if let Some(path) = std::env::var("SHELL").ok().and_then(|s| find_shell(&s)) {
+        return Ok(path);
+    }
+
+    // 回退 2：/etc/shells 中第一个存在的 shell
+    if let Ok(content) = std::fs::read_to_string("/etc/shells") {
+        if let Some(path) = parse_etc_shells(&content).into_iter().find(|p| p.is_file()) {
+            return Ok(path);
+        }
+    }
+
+    Err(anyhow::anyhow!(
+        "No usable shell found (requested: {})",
+        shell_type.unwrap_or("default")
+    ))
+}
+
+// 查找 shell：绝对路径直接检查，名称则在 PATH 和常见目录中搜索
+fn find_shell(shell: &str) -> Option<PathBuf> {
+    let path = Path::new(shell);
+    if path.is_absolute() {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+
+    let mut dirs: Vec<PathBuf> = std::env::var_os("PATH")
+        .map(|p| std::env::split_paths(&p).collect())
+        .unwrap_or_default();
+    for dir in ["/bin", "/usr/bin", "/usr/local/bin", "/opt/homebrew/bin"] {
+        dirs.push(PathBuf::from(dir));
+    }
+
+    dirs.into_iter()
+        .map(|dir| dir.join(shell))
+        .find(|candidate| candidate.is_file())
+}
+
+// 解析 /etc/shells 内容（忽略注释和空行）
+fn parse_etc_shells(content: &str) -> Vec<PathBuf> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(PathBuf::from)
+        .collect()
 }
 
 // 辅助函数：解析 OSC 7 序列
@@ -493,3 +1850,300 @@ fn parse_osc7_sequence(data: &str) -> Option<String> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_kind_from_path() {
+        assert_eq!(ShellKind::from_path(Path::new("/bin/zsh")), ShellKind::Zsh);
+        assert_eq!(
+            ShellKind::from_path(Path::new("/usr/bin/bash")),
+            ShellKind::Bash
+        );
+        assert_eq!(
+            ShellKind::from_path(Path::new("/usr/local/bin/fish")),
+            ShellKind::Fish
+        );
+        assert_eq!(ShellKind::from_path(Path::new("/bin/dash")), ShellKind::Sh);
+        assert_eq!(ShellKind::from_path(Path::new("nu")), ShellKind::Nu);
+        assert_eq!(
+            ShellKind::from_path(Path::new("/opt/xonsh")),
+            ShellKind::Other
+        );
+        assert!(ShellKind::Sh.osc7_hook().unwrap().starts_with("PS1="));
+        assert!(ShellKind::Fish.osc7_hook().is_some());
+        assert!(ShellKind::Sh.osc133_hook().is_none());
+        assert!(ShellKind::Bash.osc133_hook().unwrap().ends_with('\n'));
+    }
+
+    #[test]
+    fn test_parse_etc_shells() {
+        let content = "# /etc/shells: valid login shells\n/bin/sh\n\n  /usr/bin/zsh  \n";
+        assert_eq!(
+            parse_etc_shells(content),
+            vec![PathBuf::from("/bin/sh"), PathBuf::from("/usr/bin/zsh")]
+        );
+    }
+
+    // 测试用输出：中文、emoji、SGR 颜色、以 BEL 和 ST 结束的 OSC 7、字符集切换
+    const SAMPLE_OUTPUT: &str = "你好，世界 🚀\x1b[1;32mok\x1b[0m\r\n\x1b]7;file://host/tmp/%E4%B8%AD\x07\x1b(B\x1b]7;file://host/home\x1b\\> ";
+
+    fn decode_chunks(chunks: &[&[u8]]) -> Vec<String> {
+        let mut decoder = OutputDecoder::new();
+        chunks
+            .iter()
+            .map(|chunk| {
+                let ready = decoder.feed(chunk);
+                String::from_utf8(ready).expect("decoder emitted invalid UTF-8")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_decoder_split_at_every_boundary() {
+        let bytes = SAMPLE_OUTPUT.as_bytes();
+        for split in 0..=bytes.len() {
+            let out = decode_chunks(&[&bytes[..split], &bytes[split..]]);
+            assert_eq!(out.concat(), SAMPLE_OUTPUT, "split at {}", split);
+
+            // 每个 OSC 7 序列都完整地出现在同一段输出中
+            for chunk in &out {
+                if let Some(start) = chunk.find("\x1b]7;") {
+                    assert!(parse_osc7_sequence(&chunk[start..]).is_some());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decoder_byte_by_byte() {
+        let bytes = SAMPLE_OUTPUT.as_bytes();
+        let chunks: Vec<&[u8]> = bytes.chunks(1).collect();
+        let out = decode_chunks(&chunks);
+        assert_eq!(out.concat(), SAMPLE_OUTPUT);
+        assert!(out.iter().all(|chunk| !chunk.contains('\u{fffd}')));
+        assert!(out
+            .iter()
+            .any(|chunk| chunk == "\x1b]7;file://host/home\x1b\\"));
+    }
+
+    #[test]
+    fn test_decoder_flushes_oversized_pending() {
+        let mut decoder = OutputDecoder::new();
+        // 未结束的 OSC 序列超过上限后直接推送
+        assert!(decoder.feed(b"\x1b]0;").is_empty());
+        let long = vec![b'a'; MAX_PENDING_BYTES + 1];
+        assert_eq!(decoder.feed(&long).len(), MAX_PENDING_BYTES + 5);
+        // 无效的 UTF-8 不会被保留
+        assert_eq!(decoder.feed(&[0xff, b'x']), vec![0xff, b'x']);
+    }
+
+    #[test]
+    fn test_flow_control_waits_for_ack() {
+        let flow = Arc::new(FlowControl::new());
+        // 前端没有确认过时不限制
+        flow.sent(MAX_UNACKED_BYTES * 2);
+        flow.wait_for_window();
+
+        flow.ack(0);
+        let (done_tx, done_rx) = mpsc::channel();
+        let waiter = Arc::clone(&flow);
+        std::thread::spawn(move || {
+            waiter.wait_for_window();
+            done_tx.send(()).unwrap();
+        });
+        assert!(done_rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+        flow.ack(MAX_UNACKED_BYTES);
+        done_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    }
+
+    #[test]
+    fn test_bracketed_paste_change() {
+        assert_eq!(bracketed_paste_change(b"plain output"), None);
+        assert_eq!(bracketed_paste_change(b"\x1b[?2004h> "), Some(true));
+        assert_eq!(bracketed_paste_change(b"\x1b[?1049;2004l"), Some(false));
+        assert_eq!(
+            bracketed_paste_change(b"\x1b[?2004h\x1b[?25l\x1b[?2004l"),
+            Some(false)
+        );
+        assert_eq!(bracketed_paste_change(b"\x1b[?1h\x1b[?20045h"), None);
+    }
+
+    #[test]
+    fn test_prepare_paste() {
+        assert_eq!(prepare_paste("ls\r\npwd\n", false), "ls\rpwd\r");
+        assert_eq!(
+            prepare_paste("echo hi\nrm -rf /tmp/x\x1b[201~\n", true),
+            "\x1b[200~echo hi\rrm -rf /tmp/x\r\x1b[201~"
+        );
+    }
+
+    #[test]
+    fn test_terminal_bytes() {
+        let bytes: TerminalBytes = serde_json::from_str("\"G1tB\"").unwrap();
+        assert_eq!(bytes.into_bytes().unwrap(), b"\x1b[A");
+        let bytes: TerminalBytes = serde_json::from_str("[3, 255]").unwrap();
+        assert_eq!(bytes.into_bytes().unwrap(), vec![3, 255]);
+        let bytes: TerminalBytes = serde_json::from_str("\"not base64!\"").unwrap();
+        assert!(bytes.into_bytes().is_err());
+    }
+
+    #[test]
+    fn test_terminal_options() {
+        let options: TerminalOptions = serde_json::from_str(
+            r#"{"cwd": "~", "env": {"FOO": "1"}, "env_remove": ["SECRET"], "login": true, "cols": 120}"#,
+        )
+        .unwrap();
+        assert_eq!(options.size(), (120, 24));
+        assert!(options.login);
+        assert_eq!(options.env_remove, vec!["SECRET"]);
+        assert!(options.working_dir().unwrap().is_dir());
+
+        let defaults: TerminalOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(defaults.size(), (80, 24));
+        assert!(!defaults.login);
+
+        let missing = TerminalOptions {
+            cwd: Some("/definitely/not/a/dir".to_string()),
+            ..Default::default()
+        };
+        assert!(missing.working_dir().is_err());
+    }
+
+    #[test]
+    fn test_terminal_screen_snapshot() {
+        let mut parser = vt100::Parser::new(4, 20, 0);
+        parser.process(b"\x1b]2;demo\x07$ ls   \r\nfile.txt\r\n$ ");
+        let screen = TerminalScreen::from(parser.screen());
+        assert_eq!((screen.rows, screen.cols), (4, 20));
+        assert_eq!(screen.lines, vec!["$ ls", "file.txt", "$", ""]);
+        assert_eq!((screen.cursor_row, screen.cursor_col), (2, 2));
+        assert_eq!(screen.title, "demo");
+        assert!(!screen.alternate_screen);
+
+        // 全屏程序切换到备用屏幕并隐藏光标
+        parser.process(b"\x1b[?1049h\x1b[?25l\x1b[H\x1b[2J~\x1b[4;1H-- INSERT --");
+        let screen = TerminalScreen::from(parser.screen());
+        assert!(screen.alternate_screen);
+        assert!(!screen.cursor_visible);
+        assert_eq!(screen.lines, vec!["~", "", "", "-- INSERT --"]);
+
+        parser.process(b"\x1b[?1049l\x1b[?25h");
+        let screen = TerminalScreen::from(parser.screen());
+        assert!(!screen.alternate_screen);
+        assert_eq!(screen.lines[1], "file.txt");
+    }
+
+    #[test]
+    fn test_terminal_exit_from_status() {
+        let exit = TerminalExit::from(&ExitStatus::with_exit_code(2));
+        assert_eq!(exit.code, Some(2));
+        assert!(exit.signal.is_none());
+
+        let exit = TerminalExit::from(&ExitStatus::with_signal("Killed"));
+        assert_eq!(exit.code, None);
+        assert_eq!(exit.signal.as_deref(), Some("Killed"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_terminate_child_escalates_to_sigkill() {
+        let pair = native_pty_system()
+            .openpty(PtySize {
+                rows: 24,
+                cols: 80,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .unwrap();
+        // 忽略 SIGHUP 的进程只能被 SIGKILL 结束
+        let mut cmd = CommandBuilder::new("/bin/sh");
+        cmd.args(["-c", "trap '' HUP; sleep 30"]);
+        let mut child = pair.slave.spawn_command(cmd).unwrap();
+
+        let started = Instant::now();
+        let status = terminate_child(child.as_mut(), pair, Duration::from_millis(300)).unwrap();
+        assert!(TerminalExit::from(&status).signal.is_some());
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    // 在 PTY 中运行注入了钩子的 bash，检查实际输出的 OSC 133 标记序列
+    #[test]
+    #[cfg(unix)]
+    fn test_bash_osc133_marks() {
+        let Some(bash) = find_shell("bash") else {
+            return;
+        };
+        let pair = native_pty_system()
+            .openpty(PtySize {
+                rows: 24,
+                cols: 200,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .unwrap();
+        let mut cmd = CommandBuilder::new(bash);
+        cmd.args(["--norc", "--noprofile", "-i"]);
+        cmd.env("PS1", "> ");
+        let mut child = pair.slave.spawn_command(cmd).unwrap();
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader().unwrap();
+        let mut writer = pair.master.take_writer().unwrap();
+        let script = format!(
+            "{}{}echo one\nfalse\nexit\n",
+            ShellKind::Bash.osc7_hook().unwrap(),
+            ShellKind::Bash.osc133_hook().unwrap()
+        );
+        writer.write_all(script.as_bytes()).unwrap();
+
+        // shell 退出后读取 PTY 会返回 EIO
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut output = Vec::new();
+            let mut buf = [0u8; 4096];
+            while let Ok(n @ 1..) = reader.read(&mut buf) {
+                output.extend_from_slice(&buf[..n]);
+            }
+            let _ = tx.send(output);
+        });
+        let output = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        let _ = child.wait();
+
+        let output = String::from_utf8_lossy(&output);
+        let marks: Vec<&str> = osc_sequences(&output)
+            .into_iter()
+            .filter_map(|(_, _, payload)| payload.strip_prefix("133;"))
+            .collect();
+        assert_eq!(
+            marks,
+            [
+                "D;0",
+                "A",
+                "C;cmdline=echo one",
+                "D;0",
+                "A",
+                "C;cmdline=false",
+                "D;1",
+                "A",
+                "C;cmdline=exit",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_shell_fallback() {
+        // 不存在的 shell 应回退到系统上可用的 shell
+        if cfg!(unix) && Path::new("/bin/sh").exists() {
+            let shell = resolve_shell(Some("definitely-not-a-shell")).unwrap();
+            assert!(shell.is_file());
+            assert_eq!(
+                resolve_shell(Some("/bin/sh")).unwrap(),
+                PathBuf::from("/bin/sh")
+            );
+        }
+    }
+}
@@ -1,10 +1,36 @@
 import { defineStore } from 'pinia'
 import { ref } from 'vue'
+import { invoke } from '@tauri-apps/api/core'
 
 export const useSSHStore = defineStore('ssh', () => {
   const connections = ref([])
   const activeConnectionId = ref(null)
 
+  // 将密码保存到后端密钥存储，连接配置中只保留引用
+  async function storePassword(connection, password) {
+    if (!password) return
+    connection.passwordRef = await invoke('store_secret', {
+      id: `ssh/${connection.id}`,
+      value: password
+    })
+  }
+
+  // 迁移旧版本保存在 localStorage 中的明文密码
+  async function migratePlaintextPasswords() {
+    const legacy = connections.value.filter(c => c.password)
+    if (legacy.length === 0) return
+
+    for (const connection of legacy) {
+      try {
+        await storePassword(connection, connection.password)
+        delete connection.password
+      } catch (error) {
+        console.error(`迁移 SSH 密码失败: ${error.message || error}`)
+      }
+    }
+    saveConnections()
+  }
+
   // 从 localStorage 加载 SSH 连接
   function loadConnections() {
     try {
@@ -14,6 +40,7 @@ export const useSSHStore = defineStore('ssh', () => {
         if (Array.isArray(parsed)) {
           connections.value = parsed
           console.log(`加载了 ${connections.value.length} 个 SSH 连接配置`)
+          migratePlaintextPasswords()
           return true
         }
       }
@@ -33,7 +60,7 @@ export const useSSHStore = defineStore('ssh', () => {
   }
 
   // 创建新的 SSH 连接配置
-  function createConnection(config) {
+  async function createConnection(config) {
     const connection = {
       id: Date.now(),
       name: config.name || `${config.username}@${config.host}`,
@@ -42,10 +69,15 @@ export const useSSHStore = defineStore('ssh', () => {
       username: config.username,
       authType: config.authType || 'password', // 'password' 或 'key'
       keyPath: config.keyPath || '',
+      passwordRef: null,
       lastConnected: null,
       createdAt: Date.now()
     }
 
+    if (connection.authType === 'password') {
+      await storePassword(connection, config.password)
+    }
+
     connections.value.push(connection)
     saveConnections()
     console.log(`创建 SSH 连接: ${connection.name}`)
@@ -53,10 +85,14 @@ export const useSSHStore = defineStore('ssh', () => {
   }
 
   // 更新 SSH 连接配置
-  function updateConnection(id, updates) {
+  async function updateConnection(id, updates) {
     const connection = connections.value.find(c => c.id === id)
     if (connection) {
-      Object.assign(connection, updates)
+      const { password, ...rest } = updates
+      Object.assign(connection, rest)
+      if (connection.authType === 'password') {
+        await storePassword(connection, password)
+      }
       saveConnections()
       console.log(`更新 SSH 连接: ${connection.name}`)
     }
@@ -68,6 +104,11 @@ export const useSSHStore = defineStore('ssh', () => {
     if (index !== -1) {
       const connection = connections.value[index]
       connections.value.splice(index, 1)
+      if (connection.passwordRef) {
+        invoke('delete_secret', { id: `ssh/${connection.id}` }).catch(error =>
+          console.error(`删除 SSH 密码失败: ${error.message || error}`)
+        )
+      }
       saveConnections()
       console.log(`删除 SSH 连接: ${connection.name}`)
     }
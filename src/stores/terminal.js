@@ -1,5 +1,6 @@
 import { defineStore } from 'pinia'
 import { ref, watch } from 'vue'
+import { invoke } from '@tauri-apps/api/core'
 
 export const useTerminalStore = defineStore('terminal', () => {
   const sessions = ref([])
@@ -107,6 +108,8 @@ export const useTerminalStore = defineStore('terminal', () => {
     const newSession = {
       id,
       title,
+      // 新标签页继承当前聚焦标签页的工作目录
+      inheritCwdFrom: activeSessionId.value,
       active: true,
       warpMode: 'terminal',
       currentModel: 'gpt-4',
@@ -139,6 +142,10 @@ export const useTerminalStore = defineStore('terminal', () => {
       }
       console.log(`关闭终端: ${session.title}`)
       saveSessions()
+      // 结束后端 shell 进程（会话不存在时后端直接忽略），前台任务已在关闭标签前确认过
+      invoke('close_terminal', { sessionId: id, force: true }).catch(error =>
+        console.error(`关闭终端进程失败: ${error.message || error}`)
+      )
     }
   }
 